@@ -0,0 +1,130 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek},
+    path::Path,
+};
+
+/// Extract the regular files of a zip, tar or gzip compressed tar archive
+/// into a directory, the format is detected from the contents. Entries that
+/// would end up outside of the directory, symlinks and other special entries
+/// are skipped.
+pub(crate) fn extract(archive: &Path, target: &Path) -> io::Result<()> {
+    let mut file = File::open(archive)?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+
+    std::fs::create_dir_all(target)?;
+
+    if magic.starts_with(b"PK\x03\x04") {
+        extract_zip(file, target)
+    } else if magic.starts_with(b"\x1f\x8b") {
+        extract_tar(flate2::read::GzDecoder::new(BufReader::new(file)), target)
+    } else {
+        extract_tar(BufReader::new(file), target)
+    }
+}
+
+/// Extract the files of a zip archive.
+fn extract_zip(file: File, target: &Path) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+
+        let Some(path) = entry.enclosed_name().filter(|_| entry.is_file()) else {
+            continue;
+        };
+
+        let path = target.join(path);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // never follow or overwrite an existing file or symlink
+        let mut file = File::options().write(true).create_new(true).open(path)?;
+
+        io::copy(&mut entry, &mut file)?;
+    }
+
+    Ok(())
+}
+
+/// Extract the files of a tar archive.
+fn extract_tar(reader: impl Read, target: &Path) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if entry.header().entry_type().is_file() {
+            entry.unpack_in(target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::extract;
+
+    #[test]
+    fn archives() {
+        let base =
+            std::env::temp_dir().join(format!("memory-serve-archive-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(base.join("dist.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("index.html", options).unwrap();
+        zip.write_all(b"<p>zip</p>").unwrap();
+        zip.start_file("../escape.txt", options).unwrap();
+        zip.write_all(b"escape").unwrap();
+        zip.add_directory("assets/", options).unwrap();
+        zip.start_file("assets/index.js", options).unwrap();
+        zip.write_all(b"let a = 1;").unwrap();
+        zip.finish().unwrap();
+
+        let gzip = flate2::write::GzEncoder::new(
+            std::fs::File::create(base.join("dist.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gzip);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "assets/index.css", &b"p { x: 1 }"[..])
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        tar.append_link(&mut header, "link.txt", "/etc/passwd")
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        extract(&base.join("dist.zip"), &base.join("zip")).unwrap();
+        assert_eq!(
+            std::fs::read(base.join("zip/index.html")).unwrap(),
+            b"<p>zip</p>"
+        );
+        assert_eq!(
+            std::fs::read(base.join("zip/assets/index.js")).unwrap(),
+            b"let a = 1;"
+        );
+        assert!(!base.join("escape.txt").exists());
+
+        extract(&base.join("dist.tar.gz"), &base.join("tar")).unwrap();
+        assert_eq!(
+            std::fs::read(base.join("tar/assets/index.css")).unwrap(),
+            b"p { x: 1 }"
+        );
+        assert!(!base.join("tar/link.txt").exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
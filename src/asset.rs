@@ -1,17 +1,34 @@
 use axum::{
+    body::{Body, Bytes},
     http::{
         HeaderMap, HeaderName, HeaderValue, StatusCode,
-        header::{CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        header::{
+            CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE,
+            ETAG, LAST_MODIFIED,
+        },
     },
     response::{IntoResponse, Response},
 };
-use tracing::debug;
+use std::{
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tracing::{debug, warn};
 
 use crate::{
+    custom_headers::apply_header_rules,
+    dynamic::{self, DynamicFile},
     options::ServeOptions,
     util::{
-        compression::{compress_brotli, compress_gzip, decompress_brotli},
-        headers::{content_length, supports_encoding},
+        blocking::spawn_blocking,
+        compression::{compress_brotli_with_quality, compress_gzip, decompress_brotli},
+        headers::{content_length, is_not_modified, supports_encoding},
+        html::{generate_nonce, inject_nonce, nonce_offsets},
+        route::essence,
+        stream::{StreamEncoder, file_stream},
     },
 };
 
@@ -25,6 +42,10 @@ const GZIP_ENCODING: &str = "gzip";
 const GZIP_HEADER: (HeaderName, HeaderValue) =
     (CONTENT_ENCODING, HeaderValue::from_static(GZIP_ENCODING));
 
+/// Brotli quality used to compress responses with an injected nonce, which
+/// are compressed for every request.
+const NONCE_BROTLI_QUALITY: u32 = 5;
+
 /// Preferred compression for a dynamically served asset.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OnDemandEncoding {
@@ -37,14 +58,20 @@ enum OnDemandEncoding {
 }
 
 /// Represents a static asset that can be served
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Asset {
     /// The HTTP route used to serve the asset, e.g. `/index.html`.
     pub route: &'static str,
     /// Absolute filesystem path pointing to the source asset on disk.
     pub path: &'static str,
+    /// Canonical directory that must contain the canonical path when the
+    /// asset is loaded dynamically, unless the path is canonical itself (like
+    /// the target of a followed symlink). Empty to skip this check.
+    pub root: &'static str,
     /// Strong validator (SHA-256) used for HTTP caching semantics.
     pub etag: &'static str,
+    /// Subresource integrity (`sha384-...`) of the asset, empty when dynamic loading is used.
+    pub integrity: &'static str,
     /// MIME type advertised for the asset.
     pub content_type: &'static str,
     /// Optional embedded bytes for the asset; `None` when dynamic loading is used.
@@ -53,6 +80,30 @@ pub struct Asset {
     pub is_compressed: bool,
     /// Whether the asset should be compressed before sending to clients.
     pub should_compress: bool,
+    /// Offsets in the uncompressed HTML right after the tag name of each
+    /// `<script>` and `<style>` tag, where a CSP nonce can be inserted.
+    pub nonce_offsets: &'static [usize],
+}
+
+/// Whether the canonical path is inside the (canonical) root directory, or
+/// is the path itself, e.g. the resolved target of a followed symlink.
+fn is_inside(path: &str, root: &str, canonical: io::Result<PathBuf>) -> bool {
+    canonical.is_ok_and(|canonical| canonical == Path::new(path) || canonical.starts_with(root))
+}
+
+/// A weak ETag derived from the size and modification time of a file.
+fn metadata_etag(metadata: &Metadata, last_modified: Option<SystemTime>) -> String {
+    let modified = last_modified
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+
+    format!("W/\"{:x}-{modified:x}\"", metadata.len())
+}
+
+/// Only successful responses without a nonce can be revalidated, an error
+/// page or fallback served with another status is always sent in full.
+fn can_revalidate(status: StatusCode, nonce: bool) -> bool {
+    status == StatusCode::OK && !nonce
 }
 
 /// Aggregates response metadata and payloads for an asset request.
@@ -62,6 +113,10 @@ struct AssetResponse<'t, B> {
     status: StatusCode,
     asset: &'t Asset,
     etag: &'t str,
+    last_modified: Option<SystemTime>,
+    /// Whether a compressed body is streamed, so its length is unknown.
+    chunked: bool,
+    nonce: Option<&'t str>,
     bytes: B,
     bytes_len: usize,
     brotli_bytes: B,
@@ -71,14 +126,56 @@ struct AssetResponse<'t, B> {
 }
 
 impl<B: IntoResponse> AssetResponse<'_, B> {
-    /// Construct an Axum `Response` from the gathered asset data.
+    /// Construct an Axum `Response` from the gathered asset data, including
+    /// the security headers configured for the asset.
     fn into_response(self) -> Response {
+        let (options, asset, nonce) = (self.options, self.asset, self.nonce);
+        let (etag, last_modified) = (self.etag, self.last_modified);
+        let chunked = self.chunked;
+        let mut response = self.into_encoded_response();
+
+        if chunked && response.headers().contains_key(CONTENT_ENCODING) {
+            response.headers_mut().remove(CONTENT_LENGTH);
+        }
+
+        // the ETag of a dynamic asset is unknown if a 304 is sent before reading
+        if etag.is_empty() {
+            response.headers_mut().remove(ETAG);
+        }
+
+        if let Some(last_modified) = last_modified
+            && nonce.is_none()
+            && let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(last_modified))
+        {
+            response.headers_mut().insert(LAST_MODIFIED, value);
+        }
+
+        options
+            .security_headers
+            .apply(asset.is_html(), response.headers_mut());
+
+        // a response containing a nonce must never be reused
+        if let Some(nonce) = nonce {
+            let headers = response.headers_mut();
+            headers.remove(ETAG);
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            headers.insert(
+                CONTENT_SECURITY_POLICY,
+                options.security_headers.nonce_policy(nonce),
+            );
+        }
+
+        response
+    }
+
+    /// Pick the best encoding the client supports and build the response.
+    fn into_encoded_response(self) -> Response {
         let content_type = self.asset.content_type();
         let cache_control = self.asset.cache_control(self.options);
         let etag_header = (ETAG, HeaderValue::from_str(self.etag).unwrap());
 
-        if let Some(if_none_match) = self.headers.get(IF_NONE_MATCH)
-            && if_none_match == self.etag
+        if can_revalidate(self.status, self.nonce.is_some())
+            && is_not_modified(self.headers, self.etag, self.last_modified)
         {
             return (
                 StatusCode::NOT_MODIFIED,
@@ -138,11 +235,24 @@ impl<B: IntoResponse> AssetResponse<'_, B> {
 }
 
 impl Asset {
+    /// The Subresource Integrity string of the asset, e.g. `sha384-...`,
+    /// to use in the `integrity` attribute of `<script>` and `<link>` tags.
+    /// Returns `None` when the asset is loaded dynamically.
+    pub fn integrity(&self) -> Option<&'static str> {
+        Some(self.integrity).filter(|integrity| !integrity.is_empty())
+    }
+
+    /// Whether the asset is an HTML document.
+    pub(crate) fn is_html(&self) -> bool {
+        essence(self.content_type) == "text/html"
+    }
+
     /// Pick the cache policy for the asset based on its MIME type.
     fn cache_control(&self, options: &ServeOptions) -> (HeaderName, HeaderValue) {
-        match self.content_type {
-            "text/html" => options.html_cache_control.as_header(),
-            _ => options.cache_control.as_header(),
+        if self.is_html() {
+            options.html_cache_control.as_header()
+        } else {
+            options.cache_control.as_header()
         }
     }
 
@@ -154,7 +264,7 @@ impl Asset {
     /// Get the bytes for the asset, which is possibly compressed in the binary
     pub(crate) fn leak_bytes(
         &self,
-        options: &'static ServeOptions,
+        options: &ServeOptions,
     ) -> (&'static [u8], &'static [u8], &'static [u8]) {
         let mut uncompressed = self.bytes.unwrap_or_default();
 
@@ -177,9 +287,108 @@ impl Asset {
         (uncompressed, brotli_bytes, gzip_bytes)
     }
 
-    /// Load the asset bytes from disk, returning a `404` if the file is missing.
-    fn read_source_bytes(&self) -> Result<Vec<u8>, StatusCode> {
-        std::fs::read(self.path).map_err(|_| StatusCode::NOT_FOUND)
+    /// Check that a dynamically loaded asset can still be served: it should
+    /// be a regular file, not a symlink or special file, stay inside its asset
+    /// directory and not exceed the maximum size, unless it can be streamed.
+    pub(crate) async fn check_dynamic(
+        &self,
+        options: &ServeOptions,
+        streamable: bool,
+    ) -> Result<Metadata, StatusCode> {
+        let metadata = tokio::fs::symlink_metadata(self.path).await;
+        let inside = match metadata {
+            Ok(_) if !self.root.is_empty() => {
+                let canonical = tokio::fs::canonicalize(self.path).await;
+
+                is_inside(self.path, self.root, canonical)
+            }
+            _ => true,
+        };
+
+        self.check_metadata(metadata, inside, options, streamable)
+    }
+
+    /// Blocking variant of [`Asset::check_dynamic`], for use outside of
+    /// request handlers.
+    pub(crate) fn check_dynamic_blocking(
+        &self,
+        options: &ServeOptions,
+        streamable: bool,
+    ) -> Result<Metadata, StatusCode> {
+        let metadata = std::fs::symlink_metadata(self.path);
+        let inside = match metadata {
+            Ok(_) if !self.root.is_empty() => {
+                is_inside(self.path, self.root, std::fs::canonicalize(self.path))
+            }
+            _ => true,
+        };
+
+        self.check_metadata(metadata, inside, options, streamable)
+    }
+
+    /// Refuse a dynamically loaded asset based on the metadata of its path
+    /// and whether it resolves to a path inside the asset directory.
+    fn check_metadata(
+        &self,
+        metadata: io::Result<Metadata>,
+        inside: bool,
+        options: &ServeOptions,
+        streamable: bool,
+    ) -> Result<Metadata, StatusCode> {
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                debug!("could not read {}: {error}", self.path);
+                dynamic::remove(self.path);
+
+                return Err(StatusCode::NOT_FOUND);
+            }
+        };
+
+        let refusal = if metadata.is_symlink() {
+            Some("it is a symlink".to_string())
+        } else if !metadata.is_file() {
+            Some("it is not a regular file".to_string())
+        } else if let Some(max) = options.max_dynamic_size
+            && metadata.len() > max
+            && !(streamable && metadata.len() > options.stream_threshold)
+        {
+            Some(format!(
+                "its size of {} bytes exceeds the maximum of {max} bytes",
+                metadata.len()
+            ))
+        } else if !inside {
+            Some(format!("it is outside of {}", self.root))
+        } else {
+            None
+        };
+
+        if let Some(reason) = refusal {
+            warn!("refusing to serve {}, {reason}", self.path);
+            dynamic::remove(self.path);
+
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        Ok(metadata)
+    }
+
+    /// Load the checked asset from disk, or from the cache when the file did
+    /// not change, returning a `404` if the file can not be read.
+    pub(crate) async fn load_dynamic(
+        &self,
+        metadata: &Metadata,
+        options: &ServeOptions,
+    ) -> Result<Arc<DynamicFile>, StatusCode> {
+        let read = async {
+            tokio::fs::read(self.path).await.map_err(|error| {
+                debug!("could not read {}: {error}", self.path);
+
+                StatusCode::NOT_FOUND
+            })
+        };
+
+        dynamic::load(self.path, metadata, options.enable_dynamic_cache, read).await
     }
 
     /// Decide which compression algorithm (if any) to use for a dynamic request.
@@ -203,40 +412,129 @@ impl Asset {
         OnDemandEncoding::Identity
     }
 
-    /// Compress the provided bytes according to the negotiated encoding.
-    fn encode_dynamic_bytes(&self, bytes: &[u8], encoding: OnDemandEncoding) -> (Vec<u8>, Vec<u8>) {
-        match encoding {
-            OnDemandEncoding::Brotli => (compress_brotli(bytes).unwrap_or_default(), Vec::new()),
-            OnDemandEncoding::Gzip => (Vec::new(), compress_gzip(bytes).unwrap_or_default()),
-            OnDemandEncoding::Identity => (Vec::new(), Vec::new()),
+    /// Insert a fresh nonce into the HTML and compress the result on the fly,
+    /// on the blocking thread pool.
+    async fn nonce_handler(
+        &self,
+        headers: &HeaderMap,
+        status: StatusCode,
+        bytes: &[u8],
+        offsets: &[usize],
+        options: &ServeOptions,
+    ) -> Response {
+        let nonce = generate_nonce();
+        let bytes = inject_nonce(bytes, offsets, &nonce);
+        let encoding = self.negotiate_dynamic_encoding(headers, options);
+
+        let (bytes, brotli_bytes, gzip_bytes) = spawn_blocking(move || match encoding {
+            OnDemandEncoding::Brotli => {
+                let brotli_bytes = compress_brotli_with_quality(&bytes, NONCE_BROTLI_QUALITY);
+
+                (bytes, brotli_bytes.unwrap_or_default(), Vec::new())
+            }
+            OnDemandEncoding::Gzip => {
+                let gzip_bytes = compress_gzip(&bytes);
+
+                (bytes, Vec::new(), gzip_bytes.unwrap_or_default())
+            }
+            OnDemandEncoding::Identity => (bytes, Vec::new(), Vec::new()),
+        })
+        .await;
+
+        AssetResponse {
+            options,
+            headers,
+            status,
+            asset: self,
+            etag: "",
+            last_modified: None,
+            chunked: false,
+            nonce: Some(&nonce),
+            bytes_len: bytes.len(),
+            bytes,
+            brotli_bytes_len: brotli_bytes.len(),
+            brotli_bytes,
+            gzip_bytes_len: gzip_bytes.len(),
+            gzip_bytes,
         }
+        .into_response()
     }
 
     /// Load an asset from disk and emit a response tailored to client encodings.
-    fn dynamic_handler(
+    /// Validators are checked first, from the metadata and a cached ETag, so
+    /// a `304 Not Modified` is sent without reading or compressing the file.
+    async fn dynamic_handler(
         &self,
         headers: &HeaderMap,
         status: StatusCode,
         options: &ServeOptions,
     ) -> Response {
-        let bytes = match self.read_source_bytes() {
-            Ok(bytes) => bytes,
+        let nonce = options.enable_csp_nonce && self.is_html();
+        let metadata = match self.check_dynamic(options, !nonce).await {
+            Ok(metadata) => metadata,
             Err(status) => return status.into_response(),
         };
 
-        let encoding = self.negotiate_dynamic_encoding(headers, options);
-        let (brotli_bytes, gzip_bytes) = self.encode_dynamic_bytes(&bytes, encoding);
+        let last_modified = metadata.modified().ok();
+        let revalidate = can_revalidate(status, nonce);
+        let stream = !nonce && metadata.len() > options.stream_threshold;
+
+        if revalidate {
+            let cached = options
+                .enable_dynamic_cache
+                .then(|| dynamic::cached(self.path, &metadata))
+                .flatten();
+            let etag = match &cached {
+                _ if stream => metadata_etag(&metadata, last_modified),
+                Some(file) => file.etag.clone(),
+                None => String::new(),
+            };
+
+            if is_not_modified(headers, &etag, last_modified) {
+                return self.not_modified_response(headers, status, options, &etag, last_modified);
+            }
+        }
+
+        if stream {
+            return self
+                .stream_handler(headers, status, &metadata, options)
+                .await;
+        }
+
+        let file = match self.load_dynamic(&metadata, options).await {
+            Ok(file) => file,
+            Err(status) => return status.into_response(),
+        };
+
+        if nonce {
+            let offsets = nonce_offsets(&file.bytes);
+
+            return self
+                .nonce_handler(headers, status, &file.bytes, &offsets, options)
+                .await;
+        }
 
-        let etag = sha256::digest(&bytes);
+        if revalidate && is_not_modified(headers, &file.etag, last_modified) {
+            return self.not_modified_response(headers, status, options, &file.etag, last_modified);
+        }
+
+        let (brotli_bytes, gzip_bytes) = match self.negotiate_dynamic_encoding(headers, options) {
+            OnDemandEncoding::Brotli => (file.brotli_bytes().await, Bytes::new()),
+            OnDemandEncoding::Gzip => (Bytes::new(), file.gzip_bytes().await),
+            OnDemandEncoding::Identity => (Bytes::new(), Bytes::new()),
+        };
 
         AssetResponse {
             options,
             headers,
             status,
             asset: self,
-            etag: &etag,
-            bytes_len: bytes.len(),
-            bytes,
+            etag: &file.etag,
+            last_modified,
+            chunked: false,
+            nonce: None,
+            bytes_len: file.bytes.len(),
+            bytes: file.bytes.clone(),
             brotli_bytes_len: brotli_bytes.len(),
             brotli_bytes,
             gzip_bytes_len: gzip_bytes.len(),
@@ -245,8 +543,96 @@ impl Asset {
         .into_response()
     }
 
+    /// Stream a large file from disk, compressing it on the fly when the
+    /// client supports it. The ETag is derived from the metadata, since the
+    /// contents are not hashed.
+    async fn stream_handler(
+        &self,
+        headers: &HeaderMap,
+        status: StatusCode,
+        metadata: &Metadata,
+        options: &ServeOptions,
+    ) -> Response {
+        let file = match tokio::fs::File::open(self.path).await {
+            Ok(file) => file,
+            Err(error) => {
+                debug!("could not read {}: {error}", self.path);
+
+                return StatusCode::NOT_FOUND.into_response();
+            }
+        };
+
+        let last_modified = metadata.modified().ok();
+        let etag = metadata_etag(metadata, last_modified);
+        let size = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+        let encoding = self.negotiate_dynamic_encoding(headers, options);
+        let encoder = match encoding {
+            OnDemandEncoding::Brotli => Some(StreamEncoder::brotli()),
+            OnDemandEncoding::Gzip => Some(StreamEncoder::gzip()),
+            OnDemandEncoding::Identity => None,
+        };
+        let chunked = encoder.is_some();
+        // an identity stream is sent with the current size as `Content-Length`
+        let length = encoder.is_none().then_some(metadata.len());
+        let body = Body::from_stream(file_stream(file, length, encoder));
+
+        // the streamed body is passed for the negotiated encoding only
+        let (bytes, brotli_bytes, gzip_bytes) = match encoding {
+            OnDemandEncoding::Brotli => (Body::empty(), body, Body::empty()),
+            OnDemandEncoding::Gzip => (Body::empty(), Body::empty(), body),
+            OnDemandEncoding::Identity => (body, Body::empty(), Body::empty()),
+        };
+        let len = |e: OnDemandEncoding| if e == encoding { size } else { 0 };
+
+        AssetResponse {
+            options,
+            headers,
+            status,
+            asset: self,
+            etag: &etag,
+            last_modified,
+            chunked,
+            nonce: None,
+            bytes_len: len(OnDemandEncoding::Identity),
+            bytes,
+            brotli_bytes_len: len(OnDemandEncoding::Brotli),
+            brotli_bytes,
+            gzip_bytes_len: len(OnDemandEncoding::Gzip),
+            gzip_bytes,
+        }
+        .into_response()
+    }
+
+    /// Respond with `304 Not Modified` to a dynamic request, without body.
+    fn not_modified_response(
+        &self,
+        headers: &HeaderMap,
+        status: StatusCode,
+        options: &ServeOptions,
+        etag: &str,
+        last_modified: Option<SystemTime>,
+    ) -> Response {
+        AssetResponse {
+            options,
+            headers,
+            status,
+            asset: self,
+            etag,
+            last_modified,
+            chunked: false,
+            nonce: None,
+            bytes_len: 0,
+            bytes: Bytes::new(),
+            brotli_bytes_len: 0,
+            brotli_bytes: Bytes::new(),
+            gzip_bytes_len: 0,
+            gzip_bytes: Bytes::new(),
+        }
+        .into_response()
+    }
+
     /// Serve an asset using either embedded bytes or on-demand loading.
-    pub(super) fn handler(
+    pub(super) async fn handler(
         &self,
         headers: &HeaderMap,
         status: StatusCode,
@@ -255,10 +641,16 @@ impl Asset {
         gzip_bytes: &'static [u8],
         options: &ServeOptions,
     ) -> Response {
-        if bytes.is_empty() {
+        if self.bytes.is_none() {
             debug!("using dynamic handler for {}", self.path);
 
-            return self.dynamic_handler(headers, status, options);
+            return self.dynamic_handler(headers, status, options).await;
+        }
+
+        if options.enable_csp_nonce && self.is_html() {
+            return self
+                .nonce_handler(headers, status, bytes, self.nonce_offsets, options)
+                .await;
         }
 
         AssetResponse {
@@ -267,6 +659,9 @@ impl Asset {
             status,
             asset: self,
             etag: self.etag,
+            last_modified: None,
+            chunked: false,
+            nonce: None,
             bytes_len: bytes.len(),
             bytes,
             brotli_bytes_len: brotli_bytes.len(),
@@ -277,3 +672,52 @@ impl Asset {
         .into_response()
     }
 }
+
+/// An asset with its bytes prepared for serving by the router.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ServedAsset {
+    pub(crate) asset: &'static Asset,
+    pub(crate) bytes: &'static [u8],
+    pub(crate) brotli_bytes: &'static [u8],
+    pub(crate) gzip_bytes: &'static [u8],
+}
+
+impl ServedAsset {
+    /// Prepare the (decompressed and compressed) bytes of an asset.
+    pub(crate) fn new(asset: &'static Asset, options: &ServeOptions) -> Self {
+        let (bytes, brotli_bytes, gzip_bytes) = asset.leak_bytes(options);
+
+        Self {
+            asset,
+            bytes,
+            brotli_bytes,
+            gzip_bytes,
+        }
+    }
+
+    /// Serve the asset on the request path with the given status, including
+    /// the custom headers of the rules matching the path.
+    pub(crate) async fn handler(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        status: StatusCode,
+        options: &ServeOptions,
+    ) -> Response {
+        let mut response = self
+            .asset
+            .handler(
+                headers,
+                status,
+                self.bytes,
+                self.brotli_bytes,
+                self.gzip_bytes,
+                options,
+            )
+            .await;
+
+        apply_header_rules(&options.headers, self.asset, path, response.headers_mut());
+
+        response
+    }
+}
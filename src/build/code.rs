@@ -1,6 +1,14 @@
 use std::path::Path;
 
-use super::{file_asset::FileAsset, list::list_assets};
+use super::{error::BuildError, file_asset::FileAsset, list::list_assets, options::BuildOptions};
+
+/// Rust keywords that can not be used as a plain identifier.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+    "try", "type", "unsafe", "use", "where", "while", "yield",
+];
 
 /// Generate code with metadata and contents for the assets
 pub fn assets_to_code(
@@ -10,16 +18,61 @@ pub fn assets_to_code(
     embed: bool,
     log: fn(&str),
 ) -> String {
+    let options = BuildOptions::default().embed(embed);
+    let assets = load_assets(asset_dir, path, &options, log).unwrap_or_else(|e| panic!("{e}"));
+
+    assets_code(&assets, out_dir, embed).expect("Unable to write file to out dir.")
+}
+
+/// List the assets in a directory, logging the embedding strategy
+pub(super) fn load_assets(
+    asset_dir: &str,
+    path: &Path,
+    options: &BuildOptions,
+    log: fn(&str),
+) -> Result<Vec<FileAsset>, BuildError> {
     log(&format!("Loading static assets from {asset_dir}"));
 
-    if embed {
+    if options.embed {
         log("Embedding assets into binary");
     } else {
         log("Not embedding assets into binary, assets will load dynamically");
     }
 
-    let assets = list_assets(path, embed, log);
+    list_assets(path, options, log)
+}
 
+/// Generate an expression with a constant function returning the index of a
+/// named directory, failing compilation (when used in a constant) for an
+/// unknown name
+pub(super) fn names_code(names: &[String]) -> String {
+    let valid = names
+        .iter()
+        .map(|name| format!("{name:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = format!("unknown memory-serve directory name, valid names are: {valid}");
+
+    let checks: String = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            format!("if memory_serve::str_eq(name, {name:?}) {{ return {index}; }}\n")
+        })
+        .collect();
+
+    format!(
+        "{{\nconst fn directory_index(name: &str) -> usize {{\n{checks}panic!({message:?})\n}}\ndirectory_index\n}}\n"
+    )
+}
+
+/// Generate the code for a slice of `Asset`s, writing the bytes that differ
+/// from the source files to the out dir
+pub(super) fn assets_code(
+    assets: &[FileAsset],
+    out_dir: &Path,
+    embed: bool,
+) -> std::io::Result<String> {
     // using a string is faster than using quote ;)
     let mut code = "&[".to_string();
 
@@ -27,19 +80,23 @@ pub fn assets_to_code(
         let FileAsset {
             route,
             path,
+            root,
             etag,
+            integrity,
             content_type,
             compressed_bytes,
+            rewritten_bytes,
             should_compress,
+            nonce_offsets,
         } = asset;
 
         let is_compressed = compressed_bytes.is_some();
 
         let bytes = if !embed {
             "None".to_string()
-        } else if let Some(compressed_bytes) = compressed_bytes {
-            let file_path = out_dir.join(&etag);
-            std::fs::write(&file_path, compressed_bytes).expect("Unable to write file to out dir.");
+        } else if let Some(bytes) = compressed_bytes.as_ref().or(rewritten_bytes.as_ref()) {
+            let file_path = out_dir.join(etag);
+            std::fs::write(&file_path, bytes)?;
 
             format!("Some(include_bytes!(r\"{}\"))", file_path.to_string_lossy())
         } else {
@@ -51,16 +108,190 @@ pub fn assets_to_code(
             memory_serve::Asset {{
                 route: r\"{route}\",
                 path: r{path:?},
-                content_type: \"{content_type}\",
+                root: r{root:?},
+                content_type: {content_type:?},
                 etag: \"{etag}\",
+                integrity: \"{integrity}\",
                 bytes: {bytes},
                 is_compressed: {is_compressed},
                 should_compress: {should_compress},
+                nonce_offsets: &{nonce_offsets:?},
             }},"
         ));
     }
 
     code.push(']');
 
+    Ok(code)
+}
+
+/// Convert a name or route to a valid Rust identifier, e.g. `/index.html`
+/// becomes `INDEX_HTML` (or `index_html` when not uppercase). A value
+/// without any alphanumeric characters becomes `_0`.
+pub(super) fn to_identifier(value: &str, uppercase: bool) -> String {
+    let mut identifier = String::new();
+
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() {
+            identifier.push(if uppercase {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            });
+        } else if !identifier.is_empty() && !identifier.ends_with('_') {
+            identifier.push('_');
+        }
+    }
+
+    let identifier = identifier.trim_end_matches('_');
+
+    if identifier.is_empty() {
+        "_0".to_string()
+    } else if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{identifier}")
+    } else if KEYWORDS.contains(&identifier) {
+        format!("{identifier}_")
+    } else {
+        identifier.to_string()
+    }
+}
+
+/// Add a numeric suffix to the identifier until it is not taken, the
+/// identifier is taken when any of the generated names is already used.
+fn unique_identifier(identifier: String, suffixes: &[&str], taken: &[String]) -> String {
+    let is_taken = |candidate: &str| {
+        suffixes
+            .iter()
+            .any(|suffix| taken.contains(&format!("{candidate}{suffix}")))
+    };
+
+    if !is_taken(&identifier) {
+        return identifier;
+    }
+
+    (2..)
+        .map(|index| format!("{identifier}_{index}"))
+        .find(|candidate| !is_taken(candidate))
+        .unwrap_or(identifier)
+}
+
+/// Generate a module with route and integrity constants for the assets.
+/// Routes and directory names that map to an identifier that is already
+/// taken get a numeric suffix, e.g. `/a-b.js` and `/a_b.js` become `A_B_JS`
+/// and `A_B_JS_2`. The module name is added to the taken module names.
+pub(super) fn routes_code(
+    name: &str,
+    assets: &[FileAsset],
+    modules: &mut Vec<String>,
+    log: fn(&str),
+) -> String {
+    let module = unique_identifier(to_identifier(name, false), &[""], modules);
+
+    if module != to_identifier(name, false) {
+        log(&format!(
+            "route constants of directory {name:?} are generated in module {module}"
+        ));
+    }
+
+    let mut code = format!("pub mod {module} {{");
+    let mut identifiers: Vec<String> = Vec::new();
+
+    modules.push(module);
+
+    for FileAsset {
+        route, integrity, ..
+    } in assets
+    {
+        let base = to_identifier(route, true);
+        let identifier = unique_identifier(base.clone(), &["", "_INTEGRITY"], &identifiers);
+
+        if identifier != base {
+            log(&format!(
+                "route constant for {route} is named {identifier}, {base} is already defined"
+            ));
+        }
+
+        code.push_str(&format!(
+            "
+    /// Route of `{route}`
+    pub const {identifier}: &str = r\"{route}\";
+    /// Subresource integrity of `{route}`, empty when assets are loaded dynamically
+    pub const {identifier}_INTEGRITY: &str = \"{integrity}\";"
+        ));
+
+        identifiers.push(format!("{identifier}_INTEGRITY"));
+        identifiers.push(identifier);
+    }
+
+    code.push_str("\n}\n");
+
     code
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FileAsset, names_code, routes_code, to_identifier};
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(to_identifier("/index.html", true), "INDEX_HTML");
+        assert_eq!(
+            to_identifier("/assets/index-B1x.css", true),
+            "ASSETS_INDEX_B1X_CSS"
+        );
+        assert_eq!(to_identifier("/404.html", true), "_404_HTML");
+        assert_eq!(to_identifier("default", false), "default");
+        assert_eq!(to_identifier("My App", false), "my_app");
+        assert_eq!(to_identifier("type", false), "type_");
+        assert_eq!(to_identifier("/", true), "_0");
+        assert_eq!(to_identifier("---", false), "_0");
+    }
+
+    #[test]
+    fn route_constants() {
+        let asset = |route: &str| FileAsset {
+            route: route.to_string(),
+            integrity: "sha384-x".to_string(),
+            ..Default::default()
+        };
+        let assets = [
+            asset("/a-b.js"),
+            asset("/a_b.js"),
+            asset("/a-b.js/integrity"),
+        ];
+        let mut modules = Vec::new();
+        let log = |_: &str| {};
+
+        let code = routes_code("my-app", &assets, &mut modules, log);
+        assert!(code.starts_with("pub mod my_app {"));
+        assert!(code.contains("pub const A_B_JS: &str = r\"/a-b.js\";"));
+        assert!(code.contains("pub const A_B_JS_2: &str = r\"/a_b.js\";"));
+        assert!(code.contains("pub const A_B_JS_INTEGRITY_2: &str = r\"/a-b.js/integrity\";"));
+        assert_eq!(code.matches("A_B_JS_INTEGRITY:").count(), 1);
+
+        let code = routes_code("My App", &assets, &mut modules, log);
+        assert!(code.starts_with("pub mod my_app_2 {"));
+        assert_eq!(modules, ["my_app", "my_app_2"]);
+
+        let code = routes_code("-", &[asset("/-"), asset("/_")], &mut modules, log);
+        assert!(code.starts_with("pub mod _0 {"));
+        assert!(code.contains("pub const _0: &str = r\"/-\";"));
+        assert!(code.contains("pub const _0_2: &str = r\"/_\";"));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            names_code(&["default".to_string(), "docs".to_string()]),
+            "{
+const fn directory_index(name: &str) -> usize {
+if memory_serve::str_eq(name, \"default\") { return 0; }
+if memory_serve::str_eq(name, \"docs\") { return 1; }
+panic!(\"unknown memory-serve directory name, valid names are: \\\"default\\\", \\\"docs\\\"\")
+}
+directory_index
+}
+"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt, io, path::PathBuf};
+
+/// An error while loading asset directories in a build script.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The `OUT_DIR` environment variable is not set, the build functions
+    /// should be called from a build script.
+    MissingOutDir,
+    /// The asset directory does not exist or can not be resolved.
+    MissingDirectory { path: PathBuf, source: io::Error },
+    /// A file (or directory) in the asset directory can not be read.
+    UnreadableFile { path: PathBuf, source: io::Error },
+    /// A file is skipped, e.g. because it is empty, reported in strict mode.
+    SkippedFile { path: PathBuf, reason: String },
+    /// A file name can not be converted to a route, e.g. because it is not
+    /// valid UTF-8.
+    InvalidRoute { path: PathBuf },
+    /// A text file is not valid UTF-8, reported in strict mode when
+    /// validation is enabled.
+    InvalidUtf8 { path: PathBuf },
+    /// A symbolic link points to one of its parent directories.
+    SymlinkCycle { path: PathBuf, target: PathBuf },
+    /// The asset directory is an archive, but the `archive` feature is not
+    /// enabled.
+    ArchiveUnsupported { path: PathBuf },
+    /// An archive can not be extracted, or the directory to extract it to
+    /// can not be created.
+    Archive { path: PathBuf, source: io::Error },
+    /// Multiple files are served on the same route.
+    DuplicateRoute { route: String },
+    /// Writing the generated code or the asset pack failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOutDir => write!(
+                f,
+                "OUT_DIR environment variable not set, make sure you call this from a build.rs"
+            ),
+            Self::MissingDirectory { path, source } => {
+                write!(
+                    f,
+                    "could not find asset directory {}: {source}",
+                    path.display()
+                )
+            }
+            Self::UnreadableFile { path, source } => {
+                write!(f, "could not read {}: {source}", path.display())
+            }
+            Self::SkippedFile { path, reason } => {
+                write!(f, "skipped file {}: {reason}", path.display())
+            }
+            Self::InvalidRoute { path } => {
+                write!(f, "could not create a route for {}", path.display())
+            }
+            Self::InvalidUtf8 { path } => write!(f, "{} is not valid UTF-8", path.display()),
+            Self::SymlinkCycle { path, target } => write!(
+                f,
+                "symlink {} points to its parent directory {}",
+                path.display(),
+                target.display()
+            ),
+            Self::ArchiveUnsupported { path } => write!(
+                f,
+                "{} is an archive, loading archives requires the archive feature",
+                path.display()
+            ),
+            Self::Archive { path, source } => {
+                write!(f, "could not extract archive {}: {source}", path.display())
+            }
+            Self::DuplicateRoute { route } => {
+                write!(f, "multiple files are served on route {route}")
+            }
+            Self::Io(error) => write!(f, "could not write build output: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MissingDirectory { source, .. }
+            | Self::UnreadableFile { source, .. }
+            | Self::Archive { source, .. } => Some(source),
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BuildError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
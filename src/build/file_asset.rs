@@ -1,13 +1,18 @@
 use std::path::PathBuf;
 
 /// Internal data structure
+#[derive(Default)]
 pub(super) struct FileAsset {
     pub(super) route: String,
     pub(super) path: PathBuf,
+    pub(super) root: PathBuf,
     pub(super) etag: String,
+    pub(super) integrity: String,
     pub(super) content_type: String,
     pub(super) compressed_bytes: Option<Vec<u8>>,
+    pub(super) rewritten_bytes: Option<Vec<u8>>,
     pub(super) should_compress: bool,
+    pub(super) nonce_offsets: Vec<usize>,
 }
 
 impl PartialEq for FileAsset {
@@ -1,106 +1,527 @@
-use std::path::Path;
+use std::{collections::HashMap, io::Read, path::Path};
 
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
 use crate::{
     options::{COMPRESS_TYPES, MIN_COMPRESS_SIZE},
     util::{
         compression::compress_brotli,
-        route::{path_to_content_type, path_to_route},
+        html::{insert_integrity, nonce_offsets},
+        integrity::sri_hash,
+        pattern::glob_match,
+        route::{essence, is_text, path_to_content_type, path_to_route},
+        sniff::{SNIFF_LENGTH, sniff_content_type},
     },
 };
 
-use super::file_asset::FileAsset;
+use super::{
+    HEADERS_FILE, REDIRECTS_FILE, error::BuildError, file_asset::FileAsset, options::BuildOptions,
+};
 
-/// List all assets in the given directory (recursively) and return a list of assets with metadata
-pub(super) fn list_assets(base_path: &Path, embed: bool, log: fn(&str)) -> Vec<FileAsset> {
-    let mut assets: Vec<FileAsset> = WalkDir::new(base_path)
+/// Walk the directory recursively, following symlinks if configured and
+/// skipping dotfiles that are not allowed.
+fn walk<'a>(
+    base_path: &Path,
+    options: &'a BuildOptions,
+    log: fn(&str),
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+    WalkDir::new(base_path)
+        .follow_links(options.follow_symlinks)
         .into_iter()
-        .filter_map(|entry| entry.ok())
-        .filter_map(|entry| {
-            let path = entry.path().to_owned();
-            let route = path_to_route(base_path, entry.path());
+        .filter_entry(move |entry| {
+            let hidden = entry.depth() > 0 && options.is_hidden(entry.file_name());
 
-            let Ok(metadata) = entry.metadata() else {
-                log(&format!(
-                    "skipping file {route}, could not get file metadata"
-                ));
-                return None;
-            };
+            if hidden {
+                log(&format!("skipping dotfile {}", entry.path().display()));
+            }
+
+            !hidden
+        })
+}
+
+/// Compute the metadata of an embedded asset from its contents, inserting
+/// integrity attributes into html when an integrity map is given.
+fn embed_asset(
+    mut asset: FileAsset,
+    mut bytes: Vec<u8>,
+    original_size: u64,
+    integrity_map: Option<&HashMap<String, String>>,
+    log: fn(&str),
+) -> FileAsset {
+    let route = &asset.route;
+    let is_html = essence(&asset.content_type) == "text/html";
+    let rewritten_bytes = integrity_map.filter(|_| is_html).and_then(|integrity_map| {
+        insert_integrity(&bytes, route, |r| integrity_map.get(r).map(String::as_str))
+    });
+
+    if let Some(rewritten_bytes) = &rewritten_bytes {
+        log(&format!("inserted integrity attributes into {route}"));
+        bytes.clone_from(rewritten_bytes);
+    }
+
+    let compressed_bytes = if asset.should_compress && !cfg!(debug_assertions) {
+        compress_brotli(&bytes)
+    } else {
+        None
+    };
+
+    if let Some(compressed_size) = compressed_bytes.as_ref().map(|b| b.len()) {
+        log(&format!(
+            "including {route} {original_size} -> {compressed_size} bytes (compressed)"
+        ));
+    } else {
+        log(&format!(
+            "including {route} {original_size} bytes (uncompressed)"
+        ));
+    }
+
+    asset.etag = sha256::digest(&bytes);
+    asset.integrity = sri_hash(&bytes);
+    asset.nonce_offsets = if is_html {
+        nonce_offsets(&bytes)
+    } else {
+        Vec::new()
+    };
+    asset.compressed_bytes = compressed_bytes;
+    asset.rewritten_bytes = rewritten_bytes;
+
+    asset
+}
+
+/// Determine the MIME type of a file: a configured override for the route,
+/// the (configured) type for the extension or, for files without extension,
+/// the sniffed or configured default type.
+fn content_type(route: &str, path: &Path, options: &BuildOptions) -> Option<String> {
+    if let Some((_, mime)) = options
+        .content_types
+        .iter()
+        .find(|(pattern, _)| glob_match(pattern, route))
+    {
+        return Some(mime.clone());
+    }
+
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+
+    if let Some((_, mime)) = options
+        .extension_types
+        .iter()
+        .find(|(e, _)| Some(e) == extension.as_ref())
+    {
+        return Some(mime.clone());
+    }
+
+    if let Some(content_type) = path_to_content_type(path) {
+        return Some(content_type);
+    }
+
+    if options.sniff_content_type {
+        let mut bytes = Vec::with_capacity(SNIFF_LENGTH);
+
+        if let Ok(file) = std::fs::File::open(path)
+            && file
+                .take(SNIFF_LENGTH as u64)
+                .read_to_end(&mut bytes)
+                .is_ok()
+        {
+            return Some(sniff_content_type(&bytes).to_string());
+        }
+    }
 
-            let original_size = metadata.len();
+    options.extensionless_content_type.clone()
+}
+
+/// Check that a file contains valid UTF-8
+fn is_utf8(path: &Path) -> bool {
+    std::fs::read(path).is_ok_and(|bytes| std::str::from_utf8(&bytes).is_ok())
+}
 
-            // skip directories
-            if !metadata.is_file() {
-                return None;
-            };
+/// List all assets in the given directory (recursively) and return a list of
+/// assets with metadata. Files that can not be served are skipped and logged,
+/// in strict mode a skipped file results in an error.
+pub(super) fn list_assets(
+    base_path: &Path,
+    options: &BuildOptions,
+    log: fn(&str),
+) -> Result<Vec<FileAsset>, BuildError> {
+    let embed = options.embed;
 
-            // skip empty
-            if original_size == 0 {
-                log(&format!("skipping file {route}: file empty"));
-                return None;
+    let skip = |error: BuildError| -> Result<(), BuildError> {
+        if options.strict {
+            return Err(error);
+        }
+
+        match &error {
+            BuildError::SkippedFile { path, reason } => {
+                log(&format!("skipping file {}: {reason}", path.display()))
             }
+            BuildError::SymlinkCycle { .. } => log(&format!("skipping {error}")),
+            error => log(&format!("skipping file, {error}")),
+        }
 
-            let Some(content_type) = path_to_content_type(entry.path()) else {
-                log(&format!(
-                    "skipping file {route}, could not determine file extension"
-                ));
-                return None;
-            };
-
-            let should_compress = COMPRESS_TYPES.contains(&content_type.as_str())
-                && metadata.len() >= MIN_COMPRESS_SIZE;
-
-            // do not load assets into the binary in debug / development mode
-            if !embed {
-                log(&format!("including {route} (dynamically)"));
-
-                return Some(FileAsset {
-                    route,
-                    path: path.to_owned(),
-                    content_type,
-                    etag: Default::default(),
-                    compressed_bytes: None,
-                    should_compress,
-                });
+        Ok(())
+    };
+
+    // the asset directory, a followed symlink may resolve to a path outside
+    // of it, which is recorded as the asset path and allowed explicitly
+    let root = base_path
+        .canonicalize()
+        .unwrap_or_else(|_| base_path.to_owned());
+    let mut assets: Vec<FileAsset> = Vec::new();
+    let mut integrity_map: HashMap<String, String> = HashMap::new();
+    let mut pending = Vec::new();
+
+    for entry in walk(base_path, options, log) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) if error.loop_ancestor().is_some() => {
+                skip(BuildError::SymlinkCycle {
+                    path: error.path().unwrap_or(base_path).to_owned(),
+                    target: error.loop_ancestor().unwrap_or(base_path).to_owned(),
+                })?;
+                continue;
+            }
+            Err(error) => {
+                let path = error.path().unwrap_or(base_path).to_owned();
+                skip(BuildError::UnreadableFile {
+                    path,
+                    source: error.into(),
+                })?;
+                continue;
+            }
+        };
+
+        if entry.path_is_symlink() && !options.follow_symlinks {
+            log(&format!("skipping symlink {}", entry.path().display()));
+            continue;
+        }
+
+        // dynamic assets are read from the resolved path, a symlink at the
+        // asset path is refused at runtime
+        let path = if options.follow_symlinks {
+            match entry.path().canonicalize() {
+                Ok(path) => path,
+                Err(source) => {
+                    skip(BuildError::UnreadableFile {
+                        path: entry.path().to_owned(),
+                        source,
+                    })?;
+                    continue;
+                }
             }
+        } else {
+            entry.path().to_owned()
+        };
 
-            let Ok(bytes) = std::fs::read(entry.path()) else {
-                log(&format!("skipping file {route}: file is not readable"));
-                return None;
-            };
+        // rule files are compiled into the router, not served
+        if entry.depth() == 1
+            && (entry.file_name() == REDIRECTS_FILE || entry.file_name() == HEADERS_FILE)
+        {
+            continue;
+        }
 
-            let etag: String = sha256::digest(&bytes);
-            let enable_compression = embed && should_compress && !cfg!(debug_assertions);
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                skip(BuildError::UnreadableFile {
+                    path,
+                    source: error.into(),
+                })?;
+                continue;
+            }
+        };
 
-            let compressed_bytes = if enable_compression {
-                compress_brotli(&bytes)
-            } else {
-                None
-            };
+        let original_size = metadata.len();
+
+        // skip directories
+        if !metadata.is_file() {
+            continue;
+        };
+
+        let Some(route) = path_to_route(base_path, entry.path()) else {
+            skip(BuildError::InvalidRoute { path })?;
+            continue;
+        };
+
+        // skip empty
+        if original_size == 0 && !options.include_empty {
+            skip(BuildError::SkippedFile {
+                path,
+                reason: "file empty".to_string(),
+            })?;
+            continue;
+        }
+
+        let Some(content_type) = content_type(&route, entry.path(), options) else {
+            skip(BuildError::SkippedFile {
+                path,
+                reason: "could not determine file extension".to_string(),
+            })?;
+            continue;
+        };
+
+        let essence = essence(&content_type).to_string();
+        let should_compress =
+            COMPRESS_TYPES.contains(&essence.as_str()) && metadata.len() >= MIN_COMPRESS_SIZE;
+
+        let content_type = if options.charset && is_text(&essence) && essence == content_type {
+            if options.validate_utf8 && !is_utf8(entry.path()) {
+                if options.strict {
+                    return Err(BuildError::InvalidUtf8 { path });
+                }
 
-            if let Some(compressed_size) = compressed_bytes.as_ref().map(|b| b.len()) {
                 log(&format!(
-                    "including {route} {original_size} -> {compressed_size} bytes (compressed)"
+                    "{route} is not valid UTF-8, serving without charset"
                 ));
+
+                content_type
             } else {
-                log(&format!(
-                    "including {route} {original_size} bytes (uncompressed)"
-                ));
+                format!("{content_type}; charset=utf-8")
             }
+        } else {
+            content_type
+        };
 
-            Some(FileAsset {
-                route: route.clone(),
+        // do not load assets into the binary in debug / development mode
+        if !embed {
+            log(&format!("including {route} (dynamically)"));
+
+            assets.push(FileAsset {
+                route,
                 path: path.to_owned(),
+                root: root.clone(),
                 content_type,
-                etag,
-                compressed_bytes,
+                etag: Default::default(),
+                integrity: Default::default(),
+                compressed_bytes: None,
+                rewritten_bytes: None,
                 should_compress,
-            })
-        })
-        .collect();
+                nonce_offsets: Vec::new(),
+            });
+            continue;
+        }
+
+        let bytes = match std::fs::read(entry.path()) {
+            Ok(bytes) => bytes,
+            Err(source) => {
+                skip(BuildError::UnreadableFile { path, source })?;
+                continue;
+            }
+        };
+
+        let asset = FileAsset {
+            route,
+            path,
+            root: root.clone(),
+            content_type,
+            should_compress,
+            ..Default::default()
+        };
+
+        if !options.insert_integrity {
+            assets.push(embed_asset(asset, bytes, original_size, None, log));
+            continue;
+        }
+
+        // html files are rewritten once the integrity of all files is known
+        if essence == "text/html" {
+            integrity_map.insert(asset.route.clone(), sri_hash(&bytes));
+            pending.push((asset, bytes, original_size));
+            continue;
+        }
+
+        let asset = embed_asset(asset, bytes, original_size, None, log);
+        integrity_map.insert(asset.route.clone(), asset.integrity.clone());
+        assets.push(asset);
+    }
+
+    for (asset, bytes, original_size) in pending {
+        assets.push(embed_asset(
+            asset,
+            bytes,
+            original_size,
+            Some(&integrity_map),
+            log,
+        ));
+    }
 
     assets.sort();
 
-    assets
+    if let Some(duplicate) = assets
+        .windows(2)
+        .find(|pair| pair[0].route == pair[1].route)
+    {
+        return Err(BuildError::DuplicateRoute {
+            route: duplicate[0].route.clone(),
+        });
+    }
+
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::list_assets;
+    use crate::build::{error::BuildError, options::BuildOptions};
+
+    #[test]
+    fn strict() {
+        let path = Path::new("./static");
+
+        let assets = list_assets(path, &BuildOptions::new(), |_| {}).unwrap();
+        assert_eq!(assets.len(), 7);
+
+        let result = list_assets(path, &BuildOptions::new().strict(true), |_| {});
+        assert!(
+            matches!(result, Err(BuildError::SkippedFile { path, .. }) if path.ends_with("empty.txt"))
+        );
+
+        let options = BuildOptions::new().strict(true).include_empty(true);
+        let assets = list_assets(path, &options, |_| {}).unwrap();
+        assert_eq!(assets.len(), 8);
+    }
+
+    #[test]
+    fn content_types() {
+        let path = Path::new("./static");
+        let options = BuildOptions::new()
+            .content_type("/assets/*.js", "application/javascript")
+            .content_type("/empty.txt", "text/markdown")
+            .include_empty(true);
+
+        let assets = list_assets(path, &options, |_| {}).unwrap();
+        let content_type = |route: &str| {
+            assets
+                .iter()
+                .find(|a| a.route == route)
+                .map(|a| a.content_type.as_str())
+        };
+
+        assert_eq!(
+            content_type("/assets/index.js"),
+            Some("application/javascript; charset=utf-8")
+        );
+        assert_eq!(
+            content_type("/empty.txt"),
+            Some("text/markdown; charset=utf-8")
+        );
+        assert_eq!(
+            content_type("/index.html"),
+            Some("text/html; charset=utf-8")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dotfiles_and_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("memory-serve-walk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let path = base.join("static");
+        std::fs::create_dir_all(path.join(".git")).unwrap();
+        std::fs::create_dir_all(path.join(".well-known")).unwrap();
+        std::fs::create_dir_all(base.join("shared")).unwrap();
+        std::fs::write(path.join("index.html"), "<p>hi</p>").unwrap();
+        std::fs::write(path.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(path.join(".git/config"), "[core]").unwrap();
+        std::fs::write(path.join(".well-known/security.txt"), "Contact: a").unwrap();
+        std::fs::write(base.join("shared/style.css"), "p {}").unwrap();
+        symlink(base.join("shared"), path.join("shared")).unwrap();
+
+        let plain = || BuildOptions::new().extensionless_content_type(Some("text/plain"));
+        let routes = |options: &BuildOptions| -> Result<Vec<String>, BuildError> {
+            Ok(list_assets(&path, options, |_| {})?
+                .into_iter()
+                .map(|asset| asset.route)
+                .collect())
+        };
+
+        assert_eq!(
+            routes(&plain()).unwrap(),
+            ["/.well-known/security.txt", "/index.html"]
+        );
+        assert_eq!(
+            routes(&plain().include_dotfiles(true)).unwrap(),
+            [
+                "/.env",
+                "/.git/config",
+                "/.well-known/security.txt",
+                "/index.html"
+            ]
+        );
+
+        let options = plain().follow_symlinks(true);
+        let assets = list_assets(&path, &options, |_| {}).unwrap();
+        let style = assets.iter().find(|a| a.route == "/shared/style.css");
+        assert_eq!(
+            style.map(|a| a.path.clone()),
+            Some(base.join("shared/style.css").canonicalize().unwrap())
+        );
+        assert_eq!(
+            style.map(|a| a.root.clone()),
+            Some(path.canonicalize().unwrap())
+        );
+
+        symlink(&path, base.join("shared/loop")).unwrap();
+        assert_eq!(routes(&options).unwrap().len(), 3);
+        assert!(matches!(
+            routes(&options.strict(true)),
+            Err(BuildError::SymlinkCycle { .. })
+        ));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn integrity() {
+        use crate::util::integrity::sri_hash;
+
+        let base =
+            std::env::temp_dir().join(format!("memory-serve-integrity-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("z")).unwrap();
+        std::fs::write(base.join("a.html"), r#"<script src="/z/app.js"></script>"#).unwrap();
+        std::fs::write(base.join("z/app.js"), "alert(1)").unwrap();
+
+        let options = BuildOptions::new().embed(true).insert_integrity(true);
+        let assets = list_assets(&base, &options, |_| {}).unwrap();
+        let html = assets.iter().find(|a| a.route == "/a.html").unwrap();
+        let script = assets.iter().find(|a| a.route == "/z/app.js").unwrap();
+        let expected = format!(
+            r#"<script integrity="{}" src="/z/app.js"></script>"#,
+            sri_hash(b"alert(1)")
+        );
+
+        assert_eq!(script.integrity, sri_hash(b"alert(1)"));
+        assert_eq!(html.rewritten_bytes.as_deref(), Some(expected.as_bytes()));
+        assert_eq!(html.integrity, sri_hash(expected.as_bytes()));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn invalid_route() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let base = std::env::temp_dir().join(format!("memory-serve-route-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("index.html"), "<p>hi</p>").unwrap();
+        std::fs::write(
+            base.join(OsStr::from_bytes(b"inv\xffalid.html")),
+            "<p>hi</p>",
+        )
+        .unwrap();
+
+        let assets = list_assets(&base, &BuildOptions::new(), |_| {}).unwrap();
+        assert_eq!(assets.len(), 1);
+
+        let result = list_assets(&base, &BuildOptions::new().strict(true), |_| {});
+        assert!(matches!(result, Err(BuildError::InvalidRoute { .. })));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
 }
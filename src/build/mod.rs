@@ -1,36 +1,104 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 mod code;
+mod error;
 mod file_asset;
 mod list;
+mod options;
 
 const ASSET_FILE: &str = "memory_serve_assets.rs";
+const ROUTES_FILE: &str = "memory_serve_routes.rs";
+const NAMES_FILE: &str = "memory_serve_names.rs";
+const PACK_FILE: &str = "memory_serve.pack";
+const REDIRECTS_FILE: &str = "_redirects";
+const HEADERS_FILE: &str = "_headers";
 const QUIET_ENV_NAME: &str = "MEMORY_SERVE_QUIET";
 
 pub use code::assets_to_code;
+pub use error::BuildError;
+pub use options::BuildOptions;
 
 /// Load a directory of assets, keeping an administration of all files
-/// and optionally embedding them into the binary
+/// and optionally embedding them into the binary. The path can also point to a
+/// zip or (gzip compressed) tar archive, which is extracted in the `OUT_DIR`.
 pub fn load_directory<P: Into<PathBuf>>(path: P) {
-    // determine whether to dynamically load assets or embed them in the binary
-    let embed = !cfg!(debug_assertions) || cfg!(feature = "force-embed");
-
-    load_directory_with_embed(path, embed);
+    load_directory_with_options(path, &BuildOptions::default());
 }
 
 /// Load a directory of assets, optionally embedding them into the binary
 pub fn load_directory_with_embed<P: Into<PathBuf>>(path: P, embed: bool) {
-    load_names_directories(vec![("default", path)], embed);
+    load_directory_with_options(path, &BuildOptions::default().embed(embed));
+}
+
+/// Load a directory of assets using the provided build options
+pub fn load_directory_with_options<P: Into<PathBuf>>(path: P, options: &BuildOptions) {
+    load_names_directories_with_options(vec![("default", path)], options);
+}
+
+/// Load a directory of assets, returning an error instead of panicking
+pub fn try_load_directory<P: Into<PathBuf>>(path: P) -> Result<(), BuildError> {
+    try_load_directory_with_options(path, &BuildOptions::default())
+}
+
+/// Load a directory of assets using the provided build options, returning an
+/// error instead of panicking
+pub fn try_load_directory_with_options<P: Into<PathBuf>>(
+    path: P,
+    options: &BuildOptions,
+) -> Result<(), BuildError> {
+    try_load_names_directories_with_options(vec![("default", path)], options)
 }
 
 /// Load multiple named directories of assets, optionally embedding them into the binary
 pub fn load_names_directories<N, P>(named_paths: impl IntoIterator<Item = (N, P)>, embed: bool)
+where
+    N: Into<String>,
+    P: Into<PathBuf>,
+{
+    load_names_directories_with_options(named_paths, &BuildOptions::default().embed(embed));
+}
+
+/// Load multiple named directories of assets, optionally embedding them into
+/// the binary, returning an error instead of panicking
+pub fn try_load_names_directories<N, P>(
+    named_paths: impl IntoIterator<Item = (N, P)>,
+    embed: bool,
+) -> Result<(), BuildError>
+where
+    N: Into<String>,
+    P: Into<PathBuf>,
+{
+    try_load_names_directories_with_options(named_paths, &BuildOptions::default().embed(embed))
+}
+
+/// Load multiple named directories of assets using the provided build options
+pub fn load_names_directories_with_options<N, P>(
+    named_paths: impl IntoIterator<Item = (N, P)>,
+    options: &BuildOptions,
+) where
+    N: Into<String>,
+    P: Into<PathBuf>,
+{
+    if let Err(error) = try_load_names_directories_with_options(named_paths, options) {
+        panic!("{error}");
+    }
+}
+
+/// Load multiple named directories of assets using the provided build
+/// options, returning an error instead of panicking
+pub fn try_load_names_directories_with_options<N, P>(
+    named_paths: impl IntoIterator<Item = (N, P)>,
+    options: &BuildOptions,
+) -> Result<(), BuildError>
 where
     N: Into<String>,
     P: Into<PathBuf>,
 {
     let out_dir: PathBuf = std::env::var("OUT_DIR")
-        .expect("OUT_DIR environment variable not set, make sure you call this from a build.rs")
+        .map_err(|_| BuildError::MissingOutDir)?
         .into();
 
     println!("cargo::rerun-if-env-changed={QUIET_ENV_NAME}");
@@ -40,27 +108,340 @@ where
         }
     }
 
+    let pack = options.embed && options.asset_pack;
+
     // using a string is faster than using quote ;)
-    let mut code = "&[".to_string();
+    let mut directories = Vec::new();
+    let mut pack_entries = Vec::new();
+    let mut routes_code = String::new();
+    let mut modules = Vec::new();
+    let mut names = Vec::new();
+    let mut routes: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
     for (name, asset_dir) in named_paths {
-        let asset_dir = asset_dir
-            .into()
-            .canonicalize()
-            .expect("Could not canonicalize the provided path");
+        let name = name.into();
+        let asset_dir = asset_dir.into();
+        let asset_dir =
+            asset_dir
+                .canonicalize()
+                .map_err(|source| BuildError::MissingDirectory {
+                    path: asset_dir,
+                    source,
+                })?;
+        println!("cargo::rerun-if-changed={}", asset_dir.display());
+        let asset_dir = extract_archive(
+            asset_dir,
+            &out_dir.join(format!("archive_{}", names.len())),
+            log,
+        )?;
         let asset_dir_label = asset_dir.to_string_lossy();
-        let assets = code::assets_to_code(&asset_dir_label, &asset_dir, &out_dir, embed, log);
+        let assets = code::load_assets(&asset_dir_label, &asset_dir, options, log)?;
+
+        if assets.is_empty() {
+            // always shown, regardless of the quiet setting
+            println!(
+                "cargo:warning=memory-serve directory {name:?} ({asset_dir_label}) contains no assets"
+            );
+        }
 
-        println!("cargo::rerun-if-changed={asset_dir_label}");
+        if names.contains(&name) {
+            println!(
+                "cargo:warning=memory-serve directory name {name:?} is used more than once, load!({name:?}) uses the first"
+            );
+        }
 
-        code = format!("{code}(\"{}\", {assets}),", name.into());
+        let redirects = load_redirects(&asset_dir, log);
+        let headers = load_headers(&asset_dir, log);
+
+        report_clean_url_collisions(&assets, log);
+
+        for asset in &assets {
+            routes
+                .entry(asset.route.clone())
+                .or_default()
+                .push(name.clone());
+        }
+
+        routes_code.push_str(&code::routes_code(&name, &assets, &mut modules, log));
+        directories.push(format!(
+            "memory_serve::AssetDirectory {{ name: {name:?}, assets: {}, redirects: {redirects}, headers: {headers}, pack: PACK }},",
+            code::assets_code(&assets, &out_dir, options.embed && !pack)?
+        ));
+
+        if pack {
+            for asset in assets {
+                let bytes = match asset.compressed_bytes.or(asset.rewritten_bytes) {
+                    Some(bytes) => bytes,
+                    None => {
+                        std::fs::read(&asset.path).map_err(|source| BuildError::UnreadableFile {
+                            path: asset.path.clone(),
+                            source,
+                        })?
+                    }
+                };
+
+                pack_entries.push((name.clone(), asset.route, bytes));
+            }
+        }
+
+        names.push(name);
     }
 
-    code.push(']');
+    let pack_code = if pack {
+        format!(
+            "Some(&memory_serve::AssetPack {{ file_name: {PACK_FILE:?}, hash: {:?} }})",
+            write_pack(&out_dir, &pack_entries, log)?
+        )
+    } else {
+        "None".to_string()
+    };
+
+    let code = format!(
+        "{{\nconst PACK: Option<&memory_serve::AssetPack> = {pack_code};\n&[{}]\n}}",
+        directories.join("")
+    );
+
+    report_conflicts(&routes, log);
 
     let target = out_dir.join(ASSET_FILE);
 
-    std::fs::write(target, code).expect("Unable to write memory-serve asset file.");
+    std::fs::write(target, code)?;
+
+    let target = out_dir.join(ROUTES_FILE);
+
+    std::fs::write(target, routes_code)?;
+
+    let target = out_dir.join(NAMES_FILE);
+
+    std::fs::write(target, code::names_code(&names))?;
+
+    Ok(())
+}
+
+/// Extract the directory to the target directory if it is an archive,
+/// returning the directory with the assets
+#[cfg(feature = "archive")]
+fn extract_archive(path: PathBuf, target: &Path, log: fn(&str)) -> Result<PathBuf, BuildError> {
+    if !path.is_file() {
+        return Ok(path);
+    }
+
+    let failed = |source| BuildError::Archive {
+        path: path.clone(),
+        source,
+    };
+
+    if target.exists() {
+        std::fs::remove_dir_all(target).map_err(failed)?;
+    }
+
+    crate::archive::extract(&path, target).map_err(failed)?;
+
+    log(&format!(
+        "extracted archive {} to {}",
+        path.display(),
+        target.display()
+    ));
+
+    target.canonicalize().map_err(failed)
+}
+
+/// Archives are only supported with the `archive` feature
+#[cfg(not(feature = "archive"))]
+fn extract_archive(path: PathBuf, _target: &Path, _log: fn(&str)) -> Result<PathBuf, BuildError> {
+    if path.is_file() {
+        return Err(BuildError::ArchiveUnsupported { path });
+    }
+
+    Ok(path)
+}
+
+/// The assets and the contents of the `_redirects` and `_headers` files of an
+/// archive loaded at runtime
+#[cfg(feature = "archive")]
+pub(crate) struct ArchiveAssets {
+    pub(crate) assets: Vec<crate::Asset>,
+    pub(crate) redirects: Option<String>,
+    pub(crate) headers: Option<String>,
+}
+
+/// Extract an archive to a new private temporary directory and list the
+/// assets using the build options, embedding their contents, together with
+/// the contents of the `_redirects` and `_headers` files
+#[cfg(feature = "archive")]
+pub(crate) fn load_archive(
+    path: &Path,
+    options: &BuildOptions,
+) -> Result<ArchiveAssets, BuildError> {
+    let failed = |source| BuildError::Archive {
+        path: path.to_owned(),
+        source,
+    };
+
+    // removed when dropped
+    let target = tempfile::Builder::new()
+        .prefix("memory-serve-archive-")
+        .tempdir()
+        .map_err(failed)?;
+
+    crate::archive::extract(path, target.path()).map_err(failed)?;
+
+    let log = |message: &str| tracing::debug!("{message}");
+    let assets = list::list_assets(target.path(), &options.clone().embed(true), log)?
+        .into_iter()
+        .map(|fa| {
+            let route = fa.route.clone();
+
+            leak_asset(fa, true).map(|asset| crate::Asset {
+                path: format!("{}{route}", path.display()).leak(),
+                root: "",
+                ..asset
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ArchiveAssets {
+        assets,
+        redirects: std::fs::read_to_string(target.path().join(REDIRECTS_FILE)).ok(),
+        headers: std::fs::read_to_string(target.path().join(HEADERS_FILE)).ok(),
+    })
+}
+
+/// Convert a listed asset to an asset, leaking its fields. Without embedded
+/// (compressed or rewritten) bytes, the asset is loaded dynamically, unless
+/// `read` is set: then the file is read and embedded as is.
+#[cfg(any(test, feature = "archive"))]
+fn leak_asset(fa: file_asset::FileAsset, read: bool) -> Result<crate::Asset, BuildError> {
+    let is_compressed = fa.compressed_bytes.is_some();
+    let bytes = match fa.compressed_bytes.or(fa.rewritten_bytes) {
+        Some(bytes) => Some(bytes),
+        None if read => {
+            Some(
+                std::fs::read(&fa.path).map_err(|source| BuildError::UnreadableFile {
+                    path: fa.path.clone(),
+                    source,
+                })?,
+            )
+        }
+        None => None,
+    };
+
+    Ok(crate::Asset {
+        route: fa.route.leak(),
+        path: fa.path.to_string_lossy().to_string().leak(),
+        root: fa.root.to_string_lossy().to_string().leak(),
+        etag: fa.etag.leak(),
+        integrity: fa.integrity.leak(),
+        content_type: fa.content_type.leak(),
+        bytes: bytes.map(|bytes| &*bytes.leak()),
+        is_compressed,
+        should_compress: fa.should_compress,
+        nonce_offsets: fa.nonce_offsets.leak(),
+    })
+}
+
+/// Write the asset pack to the out dir and next to the binary in the target
+/// directory, returning its hash
+fn write_pack(
+    out_dir: &Path,
+    entries: &[(String, String, Vec<u8>)],
+    log: fn(&str),
+) -> Result<String, BuildError> {
+    let entries: Vec<(&str, &str, &[u8])> = entries
+        .iter()
+        .map(|(name, route, bytes)| (name.as_str(), route.as_str(), bytes.as_slice()))
+        .collect();
+    let pack = crate::pack::write_pack(&entries);
+    let hash = sha256::digest(&pack);
+
+    std::fs::write(out_dir.join(PACK_FILE), &pack)?;
+
+    // OUT_DIR is target/<profile>/build/<package>-<hash>/out
+    match out_dir.ancestors().nth(3) {
+        Some(target) if std::fs::write(target.join(PACK_FILE), &pack).is_ok() => log(&format!(
+            "wrote asset pack with {} assets to {}",
+            entries.len(),
+            target.join(PACK_FILE).display()
+        )),
+        _ => println!(
+            "cargo:warning=could not copy the asset pack next to the binary, it is available at {}",
+            out_dir.join(PACK_FILE).display()
+        ),
+    }
+
+    Ok(hash)
+}
+
+/// Report routes that are present in multiple directories, when these
+/// directories are merged (without prefix) the first directory takes precedence
+fn report_conflicts(routes: &BTreeMap<String, Vec<String>>, log: fn(&str)) {
+    for (route, names) in routes.iter().filter(|(_, names)| names.len() > 1) {
+        log(&format!(
+            "route {route} exists in directories {names:?}, when merged {:?} takes precedence",
+            names[0]
+        ));
+    }
+}
+
+/// Report HTML files that collide with a subdirectory index when both clean
+/// URLs and subdirectory indexes are enabled, e.g. `/about.html` and
+/// `/about/index.html` are both served on `/about`
+fn report_clean_url_collisions(assets: &[file_asset::FileAsset], log: fn(&str)) {
+    for asset in assets {
+        let Some(directory) = asset.route.strip_suffix("/index.html") else {
+            continue;
+        };
+
+        let page = format!("{directory}.html");
+
+        if assets.iter().any(|a| a.route == page) {
+            log(&format!(
+                "{page} and {} are both served on {directory} when clean URLs and subdirectory indexes are enabled",
+                asset.route
+            ));
+        }
+    }
+}
+
+/// Compile the rules of the `_redirects` file in the asset directory, if present
+fn load_redirects(asset_dir: &Path, log: fn(&str)) -> String {
+    let path = asset_dir.join(REDIRECTS_FILE);
+
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return "&[]".to_string();
+    };
+
+    let redirects = crate::Redirect::parse(&contents, log);
+    log(&format!(
+        "including {} redirects from {}",
+        redirects.len(),
+        path.to_string_lossy()
+    ));
+
+    let rules: Vec<String> = redirects
+        .into_iter()
+        .map(crate::Redirect::to_code)
+        .collect();
+
+    format!("&[{}]", rules.join(", "))
+}
+
+/// Compile the rules of the `_headers` file in the asset directory, if present
+fn load_headers(asset_dir: &Path, log: fn(&str)) -> String {
+    let path = asset_dir.join(HEADERS_FILE);
+
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return "&[]".to_string();
+    };
+
+    let headers = crate::custom_headers::parse_headers_file(&contents, log);
+    log(&format!(
+        "including {} headers from {}",
+        headers.len(),
+        path.to_string_lossy()
+    ));
+
+    format!("&{headers:?}")
 }
 
 #[cfg(test)]
@@ -70,24 +451,12 @@ pub(super) fn load_test_assets<P: Into<PathBuf>>(path: P) -> &'static [crate::As
         println!("{}", msg);
     }
 
-    let embed = !cfg!(debug_assertions) || cfg!(feature = "force-embed");
-    let assets = list::list_assets(&path.into(), embed, log);
+    let assets = list::list_assets(&path.into(), &BuildOptions::default(), log)
+        .expect("Could not list test assets");
 
     let assets = assets
         .into_iter()
-        .map(|fa| crate::Asset {
-            route: fa.route.leak(),
-            is_compressed: fa.compressed_bytes.is_some(),
-            path: fa.path.to_string_lossy().to_string().leak(),
-            etag: fa.etag.leak(),
-            content_type: fa.content_type.leak(),
-            bytes: fa.compressed_bytes.map(|v| {
-                let s: &'static [u8] = v.leak();
-
-                s
-            }),
-            should_compress: fa.should_compress,
-        })
+        .map(|fa| leak_asset(fa, false).expect("Could not load test asset"))
         .collect::<Vec<_>>();
 
     Box::leak(assets.into_boxed_slice())
@@ -0,0 +1,190 @@
+use std::ffi::OsStr;
+
+/// Options to configure how asset directories are loaded by a build script.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub(super) embed: bool,
+    pub(super) insert_integrity: bool,
+    pub(super) strict: bool,
+    pub(super) content_types: Vec<(String, String)>,
+    pub(super) extensionless_content_type: Option<String>,
+    pub(super) sniff_content_type: bool,
+    pub(super) include_empty: bool,
+    pub(super) extension_types: Vec<(String, String)>,
+    pub(super) charset: bool,
+    pub(super) validate_utf8: bool,
+    pub(super) include_dotfiles: bool,
+    pub(super) allowed_dotfiles: Vec<String>,
+    pub(super) follow_symlinks: bool,
+    pub(super) asset_pack: bool,
+}
+
+impl Default for BuildOptions {
+    /// Embed assets in release builds (or when the `force-embed` feature is
+    /// enabled) and load them dynamically otherwise.
+    fn default() -> Self {
+        Self {
+            embed: !cfg!(debug_assertions) || cfg!(feature = "force-embed"),
+            insert_integrity: false,
+            strict: false,
+            content_types: Vec::new(),
+            extensionless_content_type: None,
+            sniff_content_type: false,
+            include_empty: false,
+            extension_types: Vec::new(),
+            charset: true,
+            validate_utf8: false,
+            include_dotfiles: false,
+            allowed_dotfiles: vec![".well-known".to_string()],
+            follow_symlinks: false,
+            asset_pack: false,
+        }
+    }
+}
+
+impl BuildOptions {
+    /// Create the default build options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to embed the assets into the binary, or load them dynamically
+    /// from disk for every request.
+    pub fn embed(mut self, embed: bool) -> Self {
+        self.embed = embed;
+
+        self
+    }
+
+    /// Whether to add `integrity` attributes to `<script>` and `<link>` tags in
+    /// embedded HTML files that reference another embedded asset.
+    pub fn insert_integrity(mut self, insert_integrity: bool) -> Self {
+        self.insert_integrity = insert_integrity;
+
+        self
+    }
+
+    /// Whether to fail the build when a file is skipped, e.g. because it is
+    /// empty, has no extension or can not be read, instead of logging it.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+
+        self
+    }
+
+    /// Use a MIME type for the routes matching a pattern, e.g.
+    /// `content_type("/.well-known/apple-app-site-association", "application/json")`
+    /// or `content_type("/docs/*", "text/plain")`. The first matching
+    /// pattern is used.
+    pub fn content_type(mut self, pattern: impl Into<String>, mime: impl Into<String>) -> Self {
+        self.content_types.push((pattern.into(), mime.into()));
+
+        self
+    }
+
+    /// Which MIME type to use for files without extension, like `LICENSE`
+    /// or `CNAME`, by default these files are skipped.
+    pub fn extensionless_content_type(mut self, mime: Option<impl Into<String>>) -> Self {
+        self.extensionless_content_type = mime.map(Into::into);
+
+        self
+    }
+
+    /// Whether to determine the MIME type of files without extension from
+    /// their contents, before using the
+    /// [extensionless content type](BuildOptions::extensionless_content_type).
+    pub fn sniff_content_type(mut self, sniff_content_type: bool) -> Self {
+        self.sniff_content_type = sniff_content_type;
+
+        self
+    }
+
+    /// Whether to include empty files, served with `Content-Length: 0`,
+    /// by default these files are skipped.
+    pub fn include_empty(mut self, include_empty: bool) -> Self {
+        self.include_empty = include_empty;
+
+        self
+    }
+
+    /// Use a MIME type for files with an extension (without dot, case
+    /// insensitive), e.g. `extension_type("glb", "model/gltf-binary")`.
+    pub fn extension_type(mut self, extension: impl Into<String>, mime: impl Into<String>) -> Self {
+        self.extension_types
+            .push((extension.into().to_lowercase(), mime.into()));
+
+        self
+    }
+
+    /// Whether to add `; charset=utf-8` to the MIME type of text files,
+    /// like `text/html` and `application/json`. Enabled by default.
+    pub fn charset(mut self, charset: bool) -> Self {
+        self.charset = charset;
+
+        self
+    }
+
+    /// Whether to check that text files are valid UTF-8 before adding the
+    /// charset. Files that are not valid UTF-8 are logged and served without
+    /// charset, or fail the build in [strict](BuildOptions::strict) mode.
+    pub fn validate_utf8(mut self, validate_utf8: bool) -> Self {
+        self.validate_utf8 = validate_utf8;
+
+        self
+    }
+
+    /// Whether to include files and directories whose name starts with a dot,
+    /// like `.env` or `.git/`. By default these are skipped, except for the
+    /// [allowed dotfiles](BuildOptions::allow_dotfile).
+    pub fn include_dotfiles(mut self, include_dotfiles: bool) -> Self {
+        self.include_dotfiles = include_dotfiles;
+
+        self
+    }
+
+    /// Include a file or directory whose name starts with a dot, e.g.
+    /// `allow_dotfile(".well-known")`, which is allowed by default.
+    pub fn allow_dotfile(mut self, name: impl Into<String>) -> Self {
+        self.allowed_dotfiles.push(name.into());
+
+        self
+    }
+
+    /// Whether to follow symbolic links to files and directories, by default
+    /// these are skipped. Symlink cycles are skipped and logged, or fail the
+    /// build in [strict](BuildOptions::strict) mode.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+
+        self
+    }
+
+    /// Whether to write embedded assets into a single pack file instead of
+    /// including them in the binary with `include_bytes!`, which speeds up
+    /// compilation of large asset sets. The pack is written next to the
+    /// binary in the target directory, it can be shipped alongside the binary
+    /// or appended to it using [`append_asset_pack`](crate::append_asset_pack).
+    /// At startup the pack is memory-mapped and checked against a hash
+    /// compiled into the binary, which reads the whole pack once. Packs up to
+    /// 8 MiB are copied to memory instead.
+    ///
+    /// A mapped pack must not change while the server is running: replace
+    /// it atomically, by writing a new file and renaming it over the old one,
+    /// and never modify or truncate it in place. Truncating a mapped pack
+    /// crashes the server when an asset is read.
+    pub fn asset_pack(mut self, asset_pack: bool) -> Self {
+        self.asset_pack = asset_pack;
+
+        self
+    }
+
+    /// Whether a file or directory name is a dotfile that should be skipped.
+    pub(super) fn is_hidden(&self, name: &OsStr) -> bool {
+        !self.include_dotfiles
+            && name.as_encoded_bytes().starts_with(b".")
+            && !self
+                .allowed_dotfiles
+                .iter()
+                .any(|allowed| name == allowed.as_str())
+    }
+}
@@ -0,0 +1,224 @@
+use axum::{
+    http::{HeaderValue, StatusCode, Uri, header::LOCATION},
+    response::{IntoResponse, Response},
+};
+
+use crate::options::ServeOptions;
+
+/// Whether the canonical URL of a directory or clean URL ends with a slash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Serve routes without a trailing slash and do not redirect (default).
+    #[default]
+    Ignore,
+    /// Canonical URLs end with a slash, e.g. `/blog/`.
+    Always,
+    /// Canonical URLs do not end with a slash, e.g. `/blog`.
+    Never,
+}
+
+/// Policy to redirect non-canonical URLs to the canonical route of an asset,
+/// using a `308 Permanent Redirect` that preserves the query string.
+/// By default no redirects are made.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CanonicalUrls {
+    trailing_slash: TrailingSlash,
+    strip_html_extension: bool,
+    strip_index: bool,
+}
+
+impl CanonicalUrls {
+    /// Create a policy that does not redirect.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether directories (index files) and clean URLs end with a slash.
+    /// Routes with a file extension never get a trailing slash.
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+
+        self
+    }
+
+    /// Serve HTML files on clean URLs and redirect `/about.html` to `/about`.
+    pub fn strip_html_extension(mut self, strip_html_extension: bool) -> Self {
+        self.strip_html_extension = strip_html_extension;
+
+        self
+    }
+
+    /// Redirect index files to their directory, e.g. `/blog/index.html` to
+    /// `/blog`, when the index file is served on the directory, see
+    /// `MemoryServe::index_file` and `MemoryServe::index_on_subdirectories`.
+    pub fn strip_index(mut self, strip_index: bool) -> Self {
+        self.strip_index = strip_index;
+
+        self
+    }
+
+    /// Apply the trailing slash policy to an extensionless path, returning the
+    /// canonical path and the non-canonical variant, if it should redirect.
+    fn with_trailing_slash(&self, path: &str) -> (String, Option<String>) {
+        let path = path.trim_end_matches('/');
+
+        if path.is_empty() {
+            return ("/".to_string(), None);
+        }
+
+        match self.trailing_slash {
+            TrailingSlash::Ignore => (path.to_string(), None),
+            TrailingSlash::Always => (format!("{path}/"), Some(path.to_string())),
+            TrailingSlash::Never => (path.to_string(), Some(format!("{path}/"))),
+        }
+    }
+}
+
+/// The paths an asset is served on and the paths redirecting to them.
+#[derive(Debug, Default)]
+pub(crate) struct AssetPaths {
+    /// The directory path if the asset is served as an index file.
+    pub(crate) index: Option<&'static str>,
+    /// Paths on which the asset is served.
+    pub(crate) served: Vec<&'static str>,
+    /// Non-canonical paths redirecting to a canonical path.
+    pub(crate) redirects: Vec<(&'static str, &'static str)>,
+}
+
+impl AssetPaths {
+    /// Determine the paths for an asset route given the serve options.
+    /// Caution! This leaks the computed paths.
+    pub(crate) fn new(route: &'static str, options: &ServeOptions) -> Self {
+        let canonical = &options.canonical_urls;
+        let leak = |path: String| -> &'static str { path.leak() };
+        let mut paths = Self::default();
+
+        let directory = options.index_file.and_then(|index| {
+            if route == index {
+                Some("/")
+            } else if options.index_on_subdirectories && route.ends_with(index) {
+                Some(&route[..route.len() - index.len()])
+            } else {
+                None
+            }
+        });
+
+        if let Some(directory) = directory {
+            let (path, redirect) = canonical.with_trailing_slash(directory);
+            let path = leak(path);
+
+            paths.index = Some(path);
+            paths.served.push(path);
+
+            if let Some(redirect) = redirect {
+                paths.redirects.push((leak(redirect), path));
+            }
+
+            if canonical.strip_index {
+                paths.redirects.push((route, path));
+
+                return paths;
+            }
+        }
+
+        let clean = route.ends_with(".html")
+            && (options.enable_clean_url || canonical.strip_html_extension);
+
+        if !clean {
+            paths.served.push(route);
+
+            return paths;
+        }
+
+        let (path, redirect) = canonical.with_trailing_slash(&route[..route.len() - 5]);
+        let path = leak(path);
+        paths.served.push(path);
+
+        if let Some(redirect) = redirect {
+            paths.redirects.push((leak(redirect), path));
+        }
+
+        if canonical.strip_html_extension {
+            paths.redirects.push((route, path));
+        }
+
+        paths
+    }
+}
+
+/// Redirect to the canonical path, keeping the query string.
+pub(crate) fn redirect_to_canonical(uri: &Uri, path: &str) -> Response {
+    let location = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+
+    match HeaderValue::try_from(location) {
+        Ok(location) => (StatusCode::PERMANENT_REDIRECT, [(LOCATION, location)]).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetPaths, CanonicalUrls, TrailingSlash};
+    use crate::options::ServeOptions;
+
+    fn paths(
+        route: &'static str,
+        canonical_urls: CanonicalUrls,
+    ) -> (Vec<&'static str>, Vec<(&'static str, &'static str)>) {
+        let options = ServeOptions {
+            index_on_subdirectories: true,
+            canonical_urls,
+            ..Default::default()
+        };
+        let paths = AssetPaths::new(route, &options);
+
+        (paths.served, paths.redirects)
+    }
+
+    #[test]
+    fn asset_paths() {
+        let none = CanonicalUrls::new();
+        assert_eq!(paths("/a.js", none), (vec!["/a.js"], vec![]));
+        assert_eq!(paths("/about.html", none), (vec!["/about.html"], vec![]));
+        assert_eq!(
+            paths("/blog/index.html", none),
+            (vec!["/blog", "/blog/index.html"], vec![])
+        );
+
+        let never = CanonicalUrls::new()
+            .trailing_slash(TrailingSlash::Never)
+            .strip_html_extension(true)
+            .strip_index(true);
+        assert_eq!(paths("/a.js", never), (vec!["/a.js"], vec![]));
+        assert_eq!(
+            paths("/about.html", never),
+            (
+                vec!["/about"],
+                vec![("/about/", "/about"), ("/about.html", "/about")]
+            )
+        );
+        assert_eq!(
+            paths("/blog/index.html", never),
+            (
+                vec!["/blog"],
+                vec![("/blog/", "/blog"), ("/blog/index.html", "/blog")]
+            )
+        );
+        assert_eq!(
+            paths("/index.html", never),
+            (vec!["/"], vec![("/index.html", "/")])
+        );
+
+        let always = CanonicalUrls::new().trailing_slash(TrailingSlash::Always);
+        assert_eq!(
+            paths("/blog/index.html", always),
+            (
+                vec!["/blog/", "/blog/index.html"],
+                vec![("/blog", "/blog/")]
+            )
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    asset::Asset,
+    util::{pattern::glob_match, route::essence},
+};
+
+/// Selects the responses a custom header is added to.
+#[derive(Debug, Clone)]
+pub(crate) enum HeaderTarget {
+    /// Every asset response.
+    All,
+    /// Assets served on a path that matches the pattern, e.g. `/assets/*`.
+    Route(&'static str),
+    /// Assets whose MIME type matches the pattern, e.g. `text/html` or `image/*`.
+    ContentType(&'static str),
+}
+
+impl HeaderTarget {
+    /// Interpret a pattern: `*` matches everything, patterns starting with a
+    /// slash match routes and all other patterns match MIME types.
+    pub(crate) fn parse(pattern: &'static str) -> Self {
+        if pattern == "*" {
+            Self::All
+        } else if pattern.starts_with('/') {
+            Self::Route(pattern)
+        } else {
+            Self::ContentType(pattern)
+        }
+    }
+
+    /// Check whether the target applies to the given asset, served on `path`.
+    fn matches(&self, asset: &Asset, path: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Route(pattern) => glob_match(pattern, path),
+            Self::ContentType(pattern) => {
+                glob_match(pattern, asset.content_type)
+                    || glob_match(pattern, essence(asset.content_type))
+            }
+        }
+    }
+}
+
+/// A header that is added to every response matching the target.
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderRule {
+    target: HeaderTarget,
+    name: HeaderName,
+    value: HeaderValue,
+    /// The routes the rule is restricted to, e.g. the routes of a merged
+    /// instance, `None` to apply it to all assets.
+    scope: Option<Arc<HashSet<&'static str>>>,
+}
+
+impl HeaderRule {
+    /// Create a new rule, panics when the header name or value is invalid.
+    pub(crate) fn new(pattern: &'static str, name: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("invalid header name {name:?}"));
+        let value = HeaderValue::from_str(value)
+            .unwrap_or_else(|_| panic!("invalid value for header {name}: {value:?}"));
+
+        Self {
+            target: HeaderTarget::parse(pattern),
+            name,
+            value,
+            scope: None,
+        }
+    }
+}
+
+impl HeaderRule {
+    /// Move a rule that matches routes below a path prefix.
+    /// Caution! This leaks the prefixed pattern and scope.
+    pub(crate) fn with_prefix(mut self, prefix: &str) -> Self {
+        if let HeaderTarget::Route(pattern) = self.target {
+            self.target = HeaderTarget::Route(format!("{prefix}{pattern}").leak());
+        }
+
+        if let Some(scope) = &self.scope {
+            self.scope = Some(Arc::new(
+                scope
+                    .iter()
+                    .map(|route| &*format!("{prefix}{route}").leak())
+                    .collect(),
+            ));
+        }
+
+        self
+    }
+
+    /// Restrict a rule to the given routes, within its current scope.
+    pub(crate) fn with_scope(mut self, routes: &Arc<HashSet<&'static str>>) -> Self {
+        self.scope = Some(match self.scope {
+            Some(scope) => Arc::new(scope.intersection(routes).copied().collect()),
+            None => routes.clone(),
+        });
+
+        self
+    }
+
+    /// Check whether the rule applies to the given asset, served on `path`.
+    fn matches(&self, asset: &Asset, path: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_none_or(|scope| scope.contains(asset.route))
+            && self.target.matches(asset, path)
+    }
+}
+
+/// Add the headers of all rules matching the asset, served on the request
+/// path, to the response headers. Route patterns are matched against the
+/// path, so a rule for `/about` applies to `/about.html` served on a clean URL.
+/// A rule replaces a header memory-serve already set, multiple rules for
+/// the same header name are all appended.
+pub(crate) fn apply_header_rules(
+    rules: &[HeaderRule],
+    asset: &Asset,
+    path: &str,
+    headers: &mut HeaderMap,
+) {
+    let mut applied: Vec<&HeaderName> = Vec::new();
+
+    for rule in rules.iter().filter(|rule| rule.matches(asset, path)) {
+        if applied.contains(&&rule.name) {
+            headers.append(rule.name.clone(), rule.value.clone());
+        } else {
+            headers.insert(rule.name.clone(), rule.value.clone());
+            applied.push(&rule.name);
+        }
+    }
+}
+
+/// Parse the rules in a Netlify style `_headers` file: a line with a path
+/// pattern, followed by indented `Name: value` lines with the headers for
+/// that pattern. Empty lines and lines starting with `#` are ignored, invalid
+/// lines are reported using the log function and skipped.
+/// Returns a list of pattern, header name and header value.
+pub(crate) fn parse_headers_file(
+    contents: &str,
+    log: impl Fn(&str),
+) -> Vec<(String, String, String)> {
+    let mut rules = Vec::new();
+    let mut pattern: Option<&str> = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            if !trimmed.starts_with('/') {
+                log(&format!(
+                    "skipping headers on line {}: path {trimmed:?} should start with a slash",
+                    index + 1
+                ));
+                pattern = None;
+            } else {
+                pattern = Some(trimmed);
+            }
+
+            continue;
+        }
+
+        let Some(pattern) = pattern else {
+            log(&format!(
+                "skipping header on line {}: no path pattern",
+                index + 1
+            ));
+            continue;
+        };
+
+        let Some((name, value)) = trimmed.split_once(':') else {
+            log(&format!(
+                "skipping header on line {}: missing colon",
+                index + 1
+            ));
+            continue;
+        };
+
+        let (name, value) = (name.trim(), value.trim());
+
+        if HeaderName::from_bytes(name.as_bytes()).is_err() || HeaderValue::from_str(value).is_err()
+        {
+            log(&format!(
+                "skipping header on line {}: invalid header",
+                index + 1
+            ));
+            continue;
+        }
+
+        rules.push((pattern.to_string(), name.to_string(), value.to_string()));
+    }
+
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_headers_file;
+
+    #[test]
+    fn headers_file() {
+        let rules = parse_headers_file(
+            "
+# comment
+/assets/*
+  Cache-Control: max-age=31536000, immutable
+  X-Frame-Options: DENY
+
+/*
+  X-Content-Type-Options: nosniff
+  Invalid Header: value
+  Link
+invalid
+  X-Skipped: yes
+",
+            |_| {},
+        );
+
+        assert_eq!(
+            rules,
+            [
+                (
+                    "/assets/*".to_string(),
+                    "Cache-Control".to_string(),
+                    "max-age=31536000, immutable".to_string()
+                ),
+                (
+                    "/assets/*".to_string(),
+                    "X-Frame-Options".to_string(),
+                    "DENY".to_string()
+                ),
+                (
+                    "/*".to_string(),
+                    "X-Content-Type-Options".to_string(),
+                    "nosniff".to_string()
+                ),
+            ]
+        );
+    }
+}
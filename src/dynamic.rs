@@ -0,0 +1,306 @@
+use axum::body::Bytes;
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use crate::{
+    options::MAX_DYNAMIC_CACHE_SIZE,
+    util::{
+        blocking::spawn_blocking,
+        compression::{compress_brotli, compress_gzip},
+    },
+};
+
+/// Dynamically loaded files, keyed by path, reused until the modification
+/// time or size of the file changes.
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+/// Cached files with the moment they were last used and the number of bytes
+/// they keep in memory, the least recently used files are evicted when the
+/// total size exceeds the maximum.
+#[derive(Debug, Default)]
+struct Cache {
+    files: HashMap<&'static str, (Arc<DynamicFile>, u64, usize)>,
+    size: usize,
+    tick: u64,
+}
+
+impl Cache {
+    /// Get a file if it did not change according to its metadata, a changed
+    /// file is removed.
+    fn get(&mut self, path: &str, metadata: &Metadata) -> Option<Arc<DynamicFile>> {
+        self.tick += 1;
+
+        let (file, used, _) = self.files.get_mut(path)?;
+
+        if !file.is_fresh(metadata) {
+            self.remove(path);
+
+            return None;
+        }
+
+        *used = self.tick;
+
+        Some(file.clone())
+    }
+
+    /// Add a file, replacing a cached file with the same path, and evict
+    /// files while the total size exceeds the maximum.
+    fn insert(&mut self, path: &'static str, file: Arc<DynamicFile>, max_size: usize) {
+        self.remove(path);
+        self.tick += 1;
+
+        let size = file.memory_size();
+        self.size += size;
+        self.files.insert(path, (file, self.tick, size));
+        self.evict(max_size);
+    }
+
+    /// Count a compressed variant stored for a file, when that file is cached.
+    fn grow(&mut self, file: &DynamicFile, len: usize, max_size: usize) {
+        let Some((cached, _, size)) = self.files.get_mut(file.path) else {
+            return;
+        };
+
+        if std::ptr::eq(Arc::as_ptr(cached), file) {
+            *size += len;
+            self.size += len;
+            self.evict(max_size);
+        }
+    }
+
+    /// Remove a file and subtract its size from the total.
+    fn remove(&mut self, path: &str) {
+        if let Some((_, _, size)) = self.files.remove(path) {
+            self.size = self.size.saturating_sub(size);
+        }
+    }
+
+    /// Evict the least recently used files while the total size (including
+    /// the compressed variants) exceeds the maximum. The most recently used
+    /// file is kept, even when it exceeds the maximum by itself.
+    fn evict(&mut self, max_size: usize) {
+        while self.size > max_size && self.files.len() > 1 {
+            let Some(oldest) = self
+                .files
+                .iter()
+                .min_by_key(|(_, (_, used, _))| *used)
+                .map(|(path, _)| *path)
+            else {
+                break;
+            };
+
+            self.remove(oldest);
+        }
+    }
+}
+
+/// The contents of a dynamically loaded file, with its digest and lazily
+/// compressed variants.
+#[derive(Debug)]
+pub(crate) struct DynamicFile {
+    path: &'static str,
+    modified: Option<SystemTime>,
+    size: u64,
+    pub(crate) bytes: Bytes,
+    pub(crate) etag: String,
+    brotli_bytes: OnceLock<Bytes>,
+    gzip_bytes: OnceLock<Bytes>,
+}
+
+impl DynamicFile {
+    /// Hash the contents of a file read from disk.
+    fn new(path: &'static str, bytes: Vec<u8>, metadata: &Metadata) -> Self {
+        Self {
+            path,
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+            etag: sha256::digest(&bytes),
+            bytes: bytes.into(),
+            brotli_bytes: OnceLock::new(),
+            gzip_bytes: OnceLock::new(),
+        }
+    }
+
+    /// The number of bytes kept in memory, including the compressed variants.
+    fn memory_size(&self) -> usize {
+        self.bytes.len()
+            + self.brotli_bytes.get().map_or(0, Bytes::len)
+            + self.gzip_bytes.get().map_or(0, Bytes::len)
+    }
+
+    /// Whether the file on disk is unchanged since it was read.
+    fn is_fresh(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && self.modified == metadata.modified().ok()
+    }
+
+    /// The brotli compressed contents, compressed on first use.
+    pub(crate) async fn brotli_bytes(self: &Arc<Self>) -> Bytes {
+        self.compressed(|file| &file.brotli_bytes, compress_brotli)
+            .await
+    }
+
+    /// The gzip compressed contents, compressed on first use.
+    pub(crate) async fn gzip_bytes(self: &Arc<Self>) -> Bytes {
+        self.compressed(|file| &file.gzip_bytes, compress_gzip)
+            .await
+    }
+
+    /// Get a compressed variant, compressing on the blocking thread pool when
+    /// it is not stored yet.
+    async fn compressed(
+        self: &Arc<Self>,
+        variant: fn(&Self) -> &OnceLock<Bytes>,
+        compress: fn(&[u8]) -> Option<Vec<u8>>,
+    ) -> Bytes {
+        if let Some(bytes) = variant(self).get() {
+            return bytes.clone();
+        }
+
+        let file = self.clone();
+
+        spawn_blocking(move || {
+            variant(&file)
+                .get_or_init(|| file.variant(compress(&file.bytes)))
+                .clone()
+        })
+        .await
+    }
+
+    /// Store a compressed variant, adding its size to the cache total.
+    fn variant(&self, compressed: Option<Vec<u8>>) -> Bytes {
+        let bytes: Bytes = compressed.unwrap_or_default().into();
+
+        if let Ok(mut cache) = CACHE.lock() {
+            cache.grow(self, bytes.len(), MAX_DYNAMIC_CACHE_SIZE);
+        }
+
+        bytes
+    }
+}
+
+/// Get a file from the cache if it did not change according to its metadata.
+pub(crate) fn cached(path: &str, metadata: &Metadata) -> Option<Arc<DynamicFile>> {
+    CACHE.lock().ok()?.get(path, metadata)
+}
+
+/// Remove a file from the cache, e.g. because it was deleted.
+pub(crate) fn remove(path: &str) {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.remove(path);
+    }
+}
+
+/// Get a file from the cache if it did not change according to its metadata,
+/// otherwise read it using `read` (and cache it when enabled). The contents
+/// are hashed on the blocking thread pool.
+pub(crate) async fn load<E>(
+    path: &'static str,
+    metadata: &Metadata,
+    enable_cache: bool,
+    read: impl Future<Output = Result<Vec<u8>, E>>,
+) -> Result<Arc<DynamicFile>, E> {
+    if enable_cache && let Some(file) = cached(path, metadata) {
+        return Ok(file);
+    }
+
+    let bytes = read.await?;
+    let metadata = metadata.clone();
+    let file = Arc::new(spawn_blocking(move || DynamicFile::new(path, bytes, &metadata)).await);
+
+    if enable_cache && let Ok(mut cache) = CACHE.lock() {
+        cache.insert(path, file.clone(), MAX_DYNAMIC_CACHE_SIZE);
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{CACHE, Cache, DynamicFile, load};
+
+    #[tokio::test]
+    async fn cache() {
+        let path = std::env::temp_dir().join(format!("memory-serve-cache-{}", std::process::id()));
+        let path: &'static str = path.to_string_lossy().into_owned().leak();
+        let read = || tokio::fs::read(path);
+
+        std::fs::write(path, "first").unwrap();
+        let metadata = std::fs::metadata(path).unwrap();
+        let first = load(path, &metadata, true, read()).await.unwrap();
+        let cached = load(path, &metadata, true, async { Err("not read") })
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &cached));
+        assert_eq!(first.brotli_bytes().await, cached.brotli_bytes().await);
+        assert_eq!(CACHE.lock().unwrap().files[path].2, first.memory_size());
+
+        std::fs::write(path, "second").unwrap();
+        let metadata = std::fs::metadata(path).unwrap();
+        let second = load(path, &metadata, true, read()).await.unwrap();
+        assert_eq!(&second.bytes[..], b"second");
+        assert_ne!(first.etag, second.etag);
+
+        let uncached = load(path, &metadata, false, read()).await.unwrap();
+        assert!(!Arc::ptr_eq(&second, &uncached));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn eviction() {
+        let path = std::env::temp_dir().join(format!("memory-serve-evict-{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        let file = |path, size| Arc::new(DynamicFile::new(path, vec![0; size], &metadata));
+        let mut cache = Cache::default();
+
+        cache.insert("/a", file("/a", 4), 10);
+        cache.insert("/b", file("/b", 4), 10);
+        assert!(cache.get("/a", &metadata).is_some());
+
+        // the least recently used file is evicted
+        cache.insert("/c", file("/c", 4), 10);
+        assert!(cache.get("/a", &metadata).is_some());
+        assert!(cache.get("/b", &metadata).is_none());
+        assert!(cache.get("/c", &metadata).is_some());
+        assert_eq!(cache.size, 8);
+
+        // a file larger than the maximum is kept by itself
+        cache.insert("/d", file("/d", 20), 10);
+        assert_eq!(cache.files.len(), 1);
+        assert!(cache.get("/d", &metadata).is_some());
+        assert_eq!(cache.size, 20);
+
+        // compressed variants count towards the maximum
+        let a = file("/a", 4);
+        let variants = a.brotli_bytes().await.len() + a.gzip_bytes().await.len();
+        let max_size = 8 + variants;
+        let mut cache = Cache::default();
+        cache.insert("/a", a.clone(), max_size);
+        cache.insert("/b", file("/b", 4), max_size);
+        assert_eq!(cache.size, 8 + variants);
+
+        cache.get("/a", &metadata);
+        cache.insert("/c", file("/c", 4), max_size);
+        assert!(cache.get("/b", &metadata).is_none());
+        assert!(cache.get("/a", &metadata).is_some());
+        assert_eq!(cache.size, 8 + variants);
+
+        // a variant stored after caching is counted when it is stored
+        let b = file("/b", 4);
+        cache.insert("/b", b.clone(), max_size);
+        assert_eq!(cache.files.len(), 2);
+        cache.grow(&b, b.brotli_bytes().await.len(), max_size);
+        assert!(cache.get("/a", &metadata).is_none());
+        assert!(cache.get("/c", &metadata).is_none());
+        assert_eq!(cache.size, b.memory_size());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
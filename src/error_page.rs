@@ -0,0 +1,116 @@
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+
+use crate::{asset::ServedAsset, options::ServeOptions};
+
+/// Renders the embedded error pages configured using
+/// [`MemoryServe::error_page`](crate::MemoryServe::error_page), e.g. from
+/// other axum handlers. Obtained using
+/// [`MemoryServe::error_pages`](crate::MemoryServe::error_pages).
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorPages {
+    options: &'static ServeOptions,
+    pages: &'static HashMap<&'static str, ServedAsset>,
+}
+
+impl ErrorPages {
+    /// Collect the assets that can be served as error page.
+    /// Caution! This leaks memory.
+    pub(crate) fn new(
+        options: &'static ServeOptions,
+        assets: impl IntoIterator<Item = ServedAsset>,
+    ) -> Self {
+        let pages = assets
+            .into_iter()
+            .filter(|served| Self::is_page(options, served.asset.route))
+            .map(|served| (served.asset.route, served))
+            .collect();
+
+        Self {
+            options,
+            pages: Box::leak(Box::new(pages)),
+        }
+    }
+
+    /// Whether the route is one of the configured error pages, in any directory.
+    pub(crate) fn is_page(options: &ServeOptions, route: &str) -> bool {
+        options
+            .error_pages
+            .iter()
+            .any(|(_, file_name)| is_error_page(route, file_name))
+    }
+
+    /// Find the error page for a status, in the directory of the path or
+    /// the nearest parent directory that has one.
+    pub fn find(&self, status: StatusCode, path: &str) -> Option<&'static str> {
+        let (_, file_name) = self
+            .options
+            .error_pages
+            .iter()
+            .find(|(s, _)| *s == status)?;
+
+        directories(path)
+            .map(|directory| format!("{directory}{file_name}"))
+            .find_map(|route| self.pages.get_key_value(route.as_str()))
+            .map(|(route, _)| *route)
+    }
+
+    /// Render the error page for a status nearest to the path, using the
+    /// request headers for content negotiation. Responds with just the status
+    /// when no error page is configured or found.
+    pub async fn render(&self, status: StatusCode, path: &str, headers: &HeaderMap) -> Response {
+        match self.find(status, path) {
+            Some(route) => {
+                self.pages[route]
+                    .handler(path, headers, status, self.options)
+                    .await
+            }
+            None => status.into_response(),
+        }
+    }
+}
+
+/// Whether the route is an error page file in any directory.
+fn is_error_page(route: &str, file_name: &str) -> bool {
+    route
+        .strip_suffix(file_name)
+        .is_some_and(|directory| directory.ends_with('/'))
+}
+
+/// The directory of a path followed by all its parent directories, each
+/// ending with a slash, e.g. `/docs/`, `/` for `/docs/intro`.
+fn directories(path: &str) -> impl Iterator<Item = &str> {
+    let directory = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+
+    std::iter::successors(Some(directory).filter(|d| !d.is_empty()), |directory| {
+        let parent = directory.strip_suffix('/')?;
+
+        parent.rfind('/').map(|i| &parent[..=i])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directories, is_error_page};
+
+    #[test]
+    fn parent_directories() {
+        assert_eq!(
+            directories("/docs/guide/intro").collect::<Vec<_>>(),
+            ["/docs/guide/", "/docs/", "/"]
+        );
+        assert_eq!(directories("/docs/").collect::<Vec<_>>(), ["/docs/", "/"]);
+        assert_eq!(directories("/").collect::<Vec<_>>(), ["/"]);
+        assert_eq!(directories("").count(), 0);
+    }
+
+    #[test]
+    fn error_page() {
+        assert!(is_error_page("/404.html", "404.html"));
+        assert!(is_error_page("/docs/404.html", "404.html"));
+        assert!(!is_error_page("/docs/x404.html", "404.html"));
+    }
+}
@@ -0,0 +1,203 @@
+use axum::{
+    Router,
+    extract::Request,
+    http::{
+        HeaderMap, Method, StatusCode,
+        header::{ACCEPT, HeaderName},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::{
+    asset::ServedAsset,
+    error_page::ErrorPages,
+    options::ServeOptions,
+    redirect::{Redirect, apply_redirects},
+};
+
+const SEC_FETCH_MODE: HeaderName = HeaderName::from_static("sec-fetch-mode");
+
+/// Everything needed to handle requests that do not match an asset route.
+#[derive(Debug)]
+pub(crate) struct Site {
+    pub(crate) options: &'static ServeOptions,
+    pub(crate) assets: HashMap<&'static str, ServedAsset>,
+    pub(crate) error_pages: ErrorPages,
+    pub(crate) redirects: &'static [Redirect],
+    /// The complete router, used to serve rewritten requests.
+    pub(crate) router: OnceLock<Router>,
+}
+
+impl Site {
+    /// Whether requests that match no route need to be handled.
+    pub(crate) fn has_fallback(&self) -> bool {
+        self.options.fallback.is_some()
+            || !self.options.spa_fallbacks.is_empty()
+            || !self.options.error_pages.is_empty()
+            || self.options.directory_listing.is_some()
+            || self.redirects.iter().any(|r| !r.force)
+    }
+
+    /// Whether requests need to be handled before routing.
+    pub(crate) fn has_forced_redirects(&self) -> bool {
+        self.redirects.iter().any(|r| r.force)
+    }
+
+    /// Apply the forced redirect rules, before routing the request.
+    pub(crate) async fn forced_redirects(&'static self, request: Request, next: Next) -> Response {
+        let rules = self.redirects.iter().filter(|r| r.force);
+
+        match apply_redirects(rules, self.router(), request).await {
+            Ok(response) => response,
+            Err(request) => next.run(request).await,
+        }
+    }
+
+    /// Handle a request that matches no route: apply the redirect rules, the
+    /// SPA fallback for navigation requests, the directory listing, the
+    /// nearest `404` error page or the fallback.
+    pub(crate) async fn fallback(&'static self, request: Request) -> Response {
+        let rules = self.redirects.iter().filter(|r| !r.force);
+
+        let request = match apply_redirects(rules, self.router(), request).await {
+            Ok(response) => return response,
+            Err(request) => request,
+        };
+
+        if let Some(asset) = self.spa_fallback(&request) {
+            return asset
+                .handler(
+                    request.uri().path(),
+                    request.headers(),
+                    StatusCode::OK,
+                    self.options,
+                )
+                .await;
+        }
+
+        let path = request.uri().path();
+
+        if let Some(listing) = &self.options.directory_listing
+            && let Some(response) = listing.render(
+                &self.assets,
+                self.options,
+                path,
+                request.uri().query(),
+                request.headers(),
+            )
+        {
+            return response;
+        }
+
+        if self.error_pages.find(StatusCode::NOT_FOUND, path).is_some() {
+            return self
+                .error_pages
+                .render(StatusCode::NOT_FOUND, path, request.headers())
+                .await;
+        }
+
+        match self.options.fallback.and_then(|f| self.assets.get(f)) {
+            Some(asset) => {
+                asset
+                    .handler(
+                        path,
+                        request.headers(),
+                        self.options.fallback_status,
+                        self.options,
+                    )
+                    .await
+            }
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    /// Find the SPA fallback with the longest prefix matching a navigation request.
+    fn spa_fallback(&self, request: &Request) -> Option<&ServedAsset> {
+        if !is_navigation(request.method(), request.uri().path(), request.headers()) {
+            return None;
+        }
+
+        self.options
+            .spa_fallbacks
+            .iter()
+            .filter(|(prefix, _)| has_prefix(request.uri().path(), prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .and_then(|(_, route)| self.assets.get(route))
+    }
+
+    fn router(&self) -> &Router {
+        self.router.get().expect("router is set on construction")
+    }
+}
+
+/// Check whether a request looks like a browser navigation: a `GET` request
+/// for a path without file extension, with `Sec-Fetch-Mode: navigate` or,
+/// when that header is absent, accepting HTML.
+pub(crate) fn is_navigation(method: &Method, path: &str, headers: &HeaderMap) -> bool {
+    if method != Method::GET && method != Method::HEAD {
+        return false;
+    }
+
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+
+    if file_name.contains('.') {
+        return false;
+    }
+
+    if let Some(mode) = headers.get(SEC_FETCH_MODE) {
+        return mode == "navigate";
+    }
+
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains("text/html"))
+}
+
+/// Whether the path equals the prefix or is located below it.
+pub(crate) fn has_prefix(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_prefix, is_navigation};
+    use axum::http::{HeaderMap, HeaderValue, Method};
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        values
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), HeaderValue::from_static(v)))
+            .collect()
+    }
+
+    #[test]
+    fn navigation() {
+        let html = headers(&[("accept", "text/html,application/xhtml+xml,*/*;q=0.8")]);
+        let navigate = headers(&[("sec-fetch-mode", "navigate")]);
+        let script = headers(&[("sec-fetch-mode", "cors"), ("accept", "text/html")]);
+
+        assert!(is_navigation(&Method::GET, "/app/page", &html));
+        assert!(!is_navigation(&Method::POST, "/app/page", &html));
+        assert!(!is_navigation(&Method::GET, "/assets/chunk.js", &html));
+        assert!(!is_navigation(&Method::GET, "/app/page", &headers(&[])));
+        assert!(is_navigation(&Method::GET, "/app/page", &navigate));
+        assert!(!is_navigation(&Method::GET, "/app/page", &script));
+    }
+
+    #[test]
+    fn prefix() {
+        assert!(has_prefix("/app", "/app"));
+        assert!(has_prefix("/app/page", "/app/"));
+        assert!(!has_prefix("/application", "/app"));
+        assert!(has_prefix("/anything", "/"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+
+use crate::{asset::Asset, options::ServeOptions, util::compression::decompress_brotli};
+
+/// An asset with its decompressed contents, obtained using
+/// [`MemoryServe::get`](crate::MemoryServe::get) or
+/// [`MemoryServe::iter`](crate::MemoryServe::iter).
+#[derive(Debug, Clone)]
+pub struct AssetHandle {
+    asset: &'static Asset,
+    bytes: Cow<'static, [u8]>,
+    etag: Cow<'static, str>,
+    encodings: Vec<&'static str>,
+}
+
+impl AssetHandle {
+    /// Load the contents of an asset, decompressing embedded bytes or reading
+    /// the file when the asset is loaded dynamically.
+    pub(crate) fn new(asset: &'static Asset, options: &ServeOptions) -> Option<Self> {
+        let (bytes, etag): (Cow<'static, [u8]>, Cow<'static, str>) = match asset.bytes {
+            Some(bytes) if asset.is_compressed => (
+                Cow::Owned(decompress_brotli(bytes)?),
+                Cow::Borrowed(asset.etag),
+            ),
+            Some(bytes) => (Cow::Borrowed(bytes), Cow::Borrowed(asset.etag)),
+            None => {
+                asset.check_dynamic_blocking(options, false).ok()?;
+
+                (
+                    Cow::Owned(std::fs::read(asset.path).ok()?),
+                    Cow::Borrowed(""),
+                )
+            }
+        };
+
+        let etag = if etag.is_empty() {
+            Cow::Owned(sha256::digest(bytes.as_ref()))
+        } else {
+            etag
+        };
+
+        let mut encodings = Vec::new();
+
+        if asset.should_compress && options.enable_brotli {
+            encodings.push("br");
+        }
+
+        if asset.should_compress && options.enable_gzip {
+            encodings.push("gzip");
+        }
+
+        encodings.push("identity");
+
+        Some(Self {
+            asset,
+            bytes,
+            etag,
+            encodings,
+        })
+    }
+
+    /// The route of the asset, e.g. `/index.html`.
+    pub fn route(&self) -> &'static str {
+        self.asset.route
+    }
+
+    /// The MIME type of the asset.
+    pub fn content_type(&self) -> &'static str {
+        self.asset.content_type
+    }
+
+    /// The (uncompressed) contents of the asset.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The size in bytes of the uncompressed contents.
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The (SHA-256) etag of the contents.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// The content encodings the asset can be served with, in order of
+    /// preference, e.g. `["br", "gzip", "identity"]`.
+    pub fn encodings(&self) -> &[&'static str] {
+        &self.encodings
+    }
+
+    /// The underlying asset.
+    pub fn asset(&self) -> &'static Asset {
+        self.asset
+    }
+
+    /// Take the (uncompressed) contents of the asset.
+    pub fn into_bytes(self) -> Cow<'static, [u8]> {
+        self.bytes
+    }
+}
@@ -1,23 +1,60 @@
 #![allow(clippy::needless_doctest_main)]
 #![doc = include_str!("../README.md")]
 use axum::{
-    http::{HeaderMap, StatusCode},
+    extract::Request,
+    http::{HeaderMap, StatusCode, Uri},
+    middleware::{self, Next},
     routing::get,
 };
-use std::future::ready;
-use tracing::info;
+use std::{
+    collections::{HashMap, HashSet},
+    future::ready,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::{info, warn};
+
+use crate::{asset::ServedAsset, fallback::Site};
 
+#[cfg(feature = "archive")]
+mod archive;
 mod asset;
 mod build;
 mod cache_control;
+mod canonical;
+mod custom_headers;
+mod dynamic;
+mod error_page;
+mod fallback;
+mod handle;
+mod listing;
 mod load;
 mod options;
+mod pack;
+mod redirect;
+mod reply;
+mod security;
 mod util;
+mod validate;
 
 pub use crate::{
     asset::Asset,
-    build::{assets_to_code, load_directory, load_directory_with_embed, load_names_directories},
+    build::{
+        BuildError, BuildOptions, assets_to_code, load_directory, load_directory_with_embed,
+        load_directory_with_options, load_names_directories, load_names_directories_with_options,
+        try_load_directory, try_load_directory_with_options, try_load_names_directories,
+        try_load_names_directories_with_options,
+    },
     cache_control::CacheControl,
+    canonical::{CanonicalUrls, TrailingSlash},
+    error_page::ErrorPages,
+    handle::AssetHandle,
+    listing::{DirectoryListing, ListingSort},
+    load::{AssetDirectory, str_eq},
+    pack::{AssetPack, append_asset_pack},
+    redirect::Redirect,
+    reply::AssetReply,
+    security::{ContentSecurityPolicy, SecurityHeaders},
+    validate::RouteError,
 };
 
 /// Helper struct to create and configure an axum to serve static files from
@@ -27,6 +64,10 @@ pub struct MemoryServe {
     options: options::ServeOptions,
     assets: &'static [Asset],
     aliases: Vec<(&'static str, &'static str)>,
+    redirects: Vec<Redirect>,
+    /// Assets prepared for [`MemoryServe::respond`], by route and whether
+    /// gzip and brotli are enabled.
+    prepared: Mutex<HashMap<(&'static str, bool, bool), ServedAsset>>,
 }
 
 impl MemoryServe {
@@ -40,12 +81,90 @@ impl MemoryServe {
         }
     }
 
+    /// Initiate a `MemoryServe` instance from a directory generated by the
+    /// build step, used by the `load!` macro. The contents of the assets are
+    /// taken from the asset pack, if used, which panics if the pack can not
+    /// be found or does not match the binary.
+    #[doc(hidden)]
+    pub fn from_directory(directory: &'static AssetDirectory) -> Self {
+        let assets = match directory.pack {
+            Some(pack) => {
+                let index = pack::load(pack).unwrap_or_else(|error| panic!("{error}"));
+
+                directory
+                    .assets
+                    .iter()
+                    .map(|asset| Asset {
+                        bytes: Some(
+                            index
+                                .get(&(directory.name, asset.route))
+                                .copied()
+                                .unwrap_or_else(|| {
+                                    panic!("asset {} is missing from the asset pack", asset.route)
+                                }),
+                        ),
+                        ..*asset
+                    })
+                    .collect::<Vec<_>>()
+                    .leak()
+            }
+            None => directory.assets,
+        };
+
+        directory.headers.iter().fold(
+            Self::new(assets).add_redirects(directory.redirects.iter().copied()),
+            |memory_serve, (pattern, name, value)| memory_serve.header(pattern, name, value),
+        )
+    }
+
+    /// Load the assets of a zip, tar or gzip compressed tar archive at
+    /// runtime, like the build step loads a directory (including the
+    /// `_redirects` and `_headers` files) with the default build options.
+    /// The contents are embedded in memory, compressed like the build step
+    /// does. Requires the `archive` feature.
+    /// Caution! This method leaks memory to store the assets.
+    #[cfg(feature = "archive")]
+    pub fn from_archive(path: impl AsRef<std::path::Path>) -> Result<Self, BuildError> {
+        Self::from_archive_with_options(path, &BuildOptions::default())
+    }
+
+    /// Load the assets of an archive at runtime using the build options, see
+    /// [`MemoryServe::from_archive`].
+    #[cfg(feature = "archive")]
+    pub fn from_archive_with_options(
+        path: impl AsRef<std::path::Path>,
+        options: &BuildOptions,
+    ) -> Result<Self, BuildError> {
+        let build::ArchiveAssets {
+            assets,
+            redirects,
+            headers,
+        } = build::load_archive(path.as_ref(), options)?;
+        let mut memory_serve = Self::new(assets.leak());
+
+        if let Some(redirects) = redirects {
+            memory_serve = memory_serve.redirects(&redirects);
+        }
+
+        for (pattern, name, value) in headers
+            .map(|headers| custom_headers::parse_headers_file(&headers, |m| warn!("{m}")))
+            .unwrap_or_default()
+        {
+            memory_serve = memory_serve.header(pattern.leak(), &name, &value);
+        }
+
+        Ok(memory_serve)
+    }
+
     /// Which static file to serve on the route "/" (the index)
     /// The path (or route) should be relative to the directory set with
     /// the `ASSET_DIR` variable, but prepended with a slash.
-    /// By default this is `Some("/index.html")`
+    /// By default this is `Some("/index.html")`, an index file that is set
+    /// explicitly is reported by [`MemoryServe::try_into_router`] when it
+    /// matches no asset.
     pub fn index_file(mut self, index_file: Option<&'static str>) -> Self {
         self.options.index_file = index_file;
+        self.options.explicit_index_file = true;
 
         self
     }
@@ -70,6 +189,47 @@ impl MemoryServe {
         self
     }
 
+    /// Which static file to serve, with a `200 OK` status, for navigation
+    /// requests (like a user opening a page of a single page application)
+    /// that match no other route and start with the prefix, e.g.
+    /// `spa_fallback("/app", "/app/index.html")`. When multiple prefixes
+    /// match, the longest prefix is used.
+    /// Other requests, like a missing script, fall through to the
+    /// [fallback](MemoryServe::fallback), or result in a 404 response.
+    /// A request is considered a navigation when it has no file extension
+    /// and has the `Sec-Fetch-Mode: navigate` header or, without that header,
+    /// accepts `text/html`.
+    pub fn spa_fallback(mut self, prefix: &'static str, route: &'static str) -> Self {
+        self.options.spa_fallbacks.push((prefix, route));
+
+        self
+    }
+
+    /// Which file name to serve as error page for a status, e.g.
+    /// `error_page(StatusCode::NOT_FOUND, "404.html")`. The error page in the
+    /// directory of the requested path, or the nearest parent directory, is
+    /// used: a miss on `/docs/intro` serves `/docs/404.html` if it exists,
+    /// otherwise `/404.html`. `404` error pages are served for requests that
+    /// match no route (before the [fallback](MemoryServe::fallback)), other
+    /// error pages can be rendered using [`MemoryServe::error_pages`].
+    pub fn error_page(mut self, status: StatusCode, file_name: &'static str) -> Self {
+        self.options
+            .error_pages
+            .retain(|(existing, _)| *existing != status);
+        self.options.error_pages.push((status, file_name));
+
+        self
+    }
+
+    /// Serve a generated listing of the files and subdirectories on the path
+    /// of directories that are not matched by a route (like an index file),
+    /// see [`DirectoryListing`]. Disabled by default.
+    pub fn directory_listing(mut self, directory_listing: DirectoryListing) -> Self {
+        self.options.directory_listing = Some(directory_listing);
+
+        self
+    }
+
     /// What HTTP status code to return when a static file is returned by the
     /// fallback handler.
     pub fn fallback_status(mut self, fallback_status: StatusCode) -> Self {
@@ -104,6 +264,60 @@ impl MemoryServe {
         self
     }
 
+    /// Whether to insert a fresh random nonce into all `<script>` and `<style>`
+    /// tags of HTML files for every request, and send a matching
+    /// `Content-Security-Policy` header. The policy configured with
+    /// [`MemoryServe::security_headers`] is extended with the nonce, except
+    /// for directives that allow `'unsafe-inline'` or are not restricted.
+    /// HTML responses are compressed on the fly, and sent without ETag and
+    /// with `Cache-Control: no-store` since they can not be reused.
+    /// Panics when the policy extended with the nonce is not a valid header value.
+    pub fn enable_csp_nonce(mut self, enable_csp_nonce: bool) -> Self {
+        self.options.enable_csp_nonce = enable_csp_nonce;
+        self.options.security_headers = self.options.security_headers.prepare();
+
+        self
+    }
+
+    /// The maximum size (in bytes) of files read into memory in dynamic mode,
+    /// larger files are refused with a `403 Forbidden`. Files that are
+    /// streamed (see [`MemoryServe::stream_threshold`]) are not limited.
+    /// Defaults to 64 MiB, `None` disables the limit.
+    pub fn max_dynamic_size(mut self, max_dynamic_size: Option<u64>) -> Self {
+        self.options.max_dynamic_size = max_dynamic_size;
+
+        self
+    }
+
+    /// Whether to cache files served in dynamic mode in memory, with their
+    /// ETag and compressed variants, until their modification time or size
+    /// changes. The least recently used files are evicted when the cache
+    /// exceeds 256 MiB. Enabled by default.
+    pub fn enable_dynamic_cache(mut self, enable_dynamic_cache: bool) -> Self {
+        self.options.enable_dynamic_cache = enable_dynamic_cache;
+
+        self
+    }
+
+    /// Files larger than this size (in bytes) are streamed from disk in
+    /// dynamic mode instead of being read into memory, compressed on the fly
+    /// and served with an ETag derived from their size and modification time.
+    /// Defaults to 4 MiB.
+    pub fn stream_threshold(mut self, stream_threshold: u64) -> Self {
+        self.options.stream_threshold = stream_threshold;
+
+        self
+    }
+
+    /// Redirect non-canonical URLs, like `/about.html`, `/blog/` or
+    /// `/blog/index.html`, to the canonical route of the asset.
+    /// See [`CanonicalUrls`] for the options, by default no redirects are made.
+    pub fn canonical_urls(mut self, canonical_urls: CanonicalUrls) -> Self {
+        self.options.canonical_urls = canonical_urls;
+
+        self
+    }
+
     /// The Cache-Control header to set for HTML files.
     /// See [Cache control](index.html#cache-control) for options.
     pub fn html_cache_control(mut self, html_cache_control: CacheControl) -> Self {
@@ -120,6 +334,118 @@ impl MemoryServe {
         self
     }
 
+    /// Security headers to send with all responses, e.g.
+    /// `SecurityHeaders::strict()` or `SecurityHeaders::spa()`.
+    /// Headers added with [`MemoryServe::header`] take precedence.
+    /// Panics when the `Content-Security-Policy` is not a valid header value.
+    pub fn security_headers(mut self, security_headers: SecurityHeaders) -> Self {
+        self.options.security_headers = security_headers.prepare();
+
+        self
+    }
+
+    /// Add a header to responses matching a pattern. The pattern `"*"`
+    /// matches all assets, patterns starting with a slash match the request
+    /// path (e.g. `"/assets/*"`, or `"/about"` for a clean URL) and other
+    /// patterns match MIME types (e.g. `"text/html"` or `"image/*"`).
+    /// A `*` matches any sequence of characters.
+    /// Headers set this way replace the headers memory-serve sets itself,
+    /// multiple rules for the same header name are all added.
+    /// Panics when the header name or value is invalid.
+    pub fn header(mut self, pattern: &'static str, name: &str, value: &str) -> Self {
+        self.options
+            .headers
+            .push(custom_headers::HeaderRule::new(pattern, name, value));
+
+        self
+    }
+
+    /// Serve all assets, aliases, redirect rules and header rules of this
+    /// instance below a path prefix, e.g. `/shared`, so `/logo.svg` is served
+    /// on `/shared/logo.svg`. Use this before [`MemoryServe::merge`].
+    /// Caution! This method leaks memory to store the prefixed routes.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+
+        if prefix.is_empty() {
+            return self;
+        }
+
+        let with_prefix = |path: &str| -> &'static str { format!("{prefix}{path}").leak() };
+
+        self.assets = self
+            .assets
+            .iter()
+            .map(|asset| Asset {
+                route: with_prefix(asset.route),
+                ..*asset
+            })
+            .collect::<Vec<_>>()
+            .leak();
+
+        for (from, to) in &mut self.aliases {
+            *from = with_prefix(from);
+            *to = with_prefix(to);
+        }
+
+        for redirect in &mut self.redirects {
+            redirect.from = with_prefix(redirect.from);
+
+            if redirect.to.starts_with('/') {
+                redirect.to = with_prefix(redirect.to);
+            }
+        }
+
+        self.options.headers = std::mem::take(&mut self.options.headers)
+            .into_iter()
+            .map(|rule| rule.with_prefix(prefix))
+            .collect();
+
+        self
+    }
+
+    /// Combine the assets of another instance, e.g. another named directory,
+    /// with the assets of this instance. When both contain an asset with the
+    /// same route, the asset of this instance takes precedence and the
+    /// conflict is logged. The aliases, redirect rules and header rules of
+    /// the other instance are added after the ones of this instance, its
+    /// header rules only apply to its merged assets. Its other configuration
+    /// is ignored.
+    /// Caution! This method leaks memory to store the combined assets.
+    pub fn merge(mut self, other: MemoryServe) -> Self {
+        let mut assets = self.assets.to_vec();
+        let mut merged = HashSet::new();
+
+        for asset in other.assets {
+            if self.assets.iter().any(|a| a.route == asset.route) {
+                warn!(
+                    "not merging {}, overridden by {}",
+                    asset.path,
+                    self.find(asset.route).map_or("", |a| a.path)
+                );
+            } else {
+                assets.push(*asset);
+                merged.insert(asset.route);
+            }
+        }
+
+        // the header rules of the other instance only apply to its assets
+        let merged = Arc::new(merged);
+
+        self.assets = assets.leak();
+        self.aliases.extend(other.aliases);
+        self.redirects.extend(other.redirects);
+        self.options.headers.extend(
+            other
+                .options
+                .headers
+                .into_iter()
+                .map(|rule| rule.with_scope(&merged)),
+        );
+
+        self
+    }
+
     /// Create an alias for a route / file
     pub fn add_alias(mut self, from: &'static str, to: &'static str) -> Self {
         self.aliases.push((from, to));
@@ -127,19 +453,168 @@ impl MemoryServe {
         self
     }
 
+    /// Add a redirect or rewrite rule, see [`Redirect`]. Rules are applied in
+    /// the order they are added, after the rules of a `_redirects` file in
+    /// the asset directory.
+    pub fn add_redirect(mut self, redirect: Redirect) -> Self {
+        self.redirects.push(redirect);
+
+        self
+    }
+
+    /// Add multiple redirect or rewrite rules, see [`MemoryServe::add_redirect`].
+    pub fn add_redirects(mut self, redirects: impl IntoIterator<Item = Redirect>) -> Self {
+        self.redirects.extend(redirects);
+
+        self
+    }
+
+    /// Add the rules in the format of a Netlify style `_redirects` file, see
+    /// [Redirects](index.html#redirects). Invalid rules are logged and skipped.
+    /// Caution! This method leaks memory to store the rules.
+    pub fn redirects(self, rules: &str) -> Self {
+        let redirects = Redirect::parse(rules, |message| warn!("{message}"));
+
+        self.add_redirects(redirects)
+    }
+
+    /// Get the (decompressed) contents and metadata of the asset with the
+    /// given route or alias, e.g. `get("/index.html")`.
+    pub fn get(&self, route: &str) -> Option<AssetHandle> {
+        self.find(route)
+            .and_then(|asset| AssetHandle::new(asset, &self.options))
+    }
+
+    /// Serve the asset with the given route or alias from a custom handler,
+    /// using the request headers for content negotiation, e.g.
+    /// `memory_serve.respond("/report.pdf", &headers)`.
+    /// The (decompressed and compressed) bytes are prepared once per asset,
+    /// like the router does.
+    /// Caution! This method leaks memory to store the prepared bytes.
+    pub fn respond(&self, route: &str, headers: &HeaderMap) -> Option<AssetReply<'_>> {
+        let asset = self.find(route)?;
+        let key = (
+            asset.route,
+            self.options.enable_gzip,
+            self.options.enable_brotli,
+        );
+        let served = *self
+            .prepared
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .entry(key)
+            .or_insert_with(|| ServedAsset::new(asset, &self.options));
+
+        Some(AssetReply::new(served, route, &self.options, headers))
+    }
+
+    /// Find the asset with the given route or alias.
+    fn find(&self, route: &str) -> Option<&'static Asset> {
+        let route = self
+            .aliases
+            .iter()
+            .find(|(from, _)| *from == route)
+            .map_or(route, |(_, to)| to);
+
+        self.assets.iter().find(|asset| asset.route == route)
+    }
+
+    /// Iterate over all assets, the contents are loaded when the iterator advances.
+    pub fn iter(&self) -> impl Iterator<Item = AssetHandle> + '_ {
+        self.assets
+            .iter()
+            .filter_map(|asset| AssetHandle::new(asset, &self.options))
+    }
+
+    /// Create an [`ErrorPages`] instance to render the configured error pages
+    /// from other handlers, call this after configuring the error pages.
+    /// Caution! This method leaks memory. It should only be called once (at startup).
+    pub fn error_pages(&self) -> ErrorPages {
+        let options: &'static options::ServeOptions = Box::leak(Box::new(self.options.clone()));
+
+        ErrorPages::new(
+            options,
+            self.assets
+                .iter()
+                .filter(|asset| ErrorPages::is_page(options, asset.route))
+                .map(|asset| ServedAsset::new(asset, options)),
+        )
+    }
+
+    /// Check the configuration for colliding routes, aliases pointing to
+    /// missing routes and index or fallback settings matching no asset,
+    /// before creating the router, see [`MemoryServe::into_router`].
+    /// Caution! This method leaks memory. It should only be called once (at startup).
+    pub fn try_into_router<S>(self) -> Result<axum::Router<S>, Vec<RouteError>>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let paths = self.asset_paths();
+        let errors = validate::validate(self.assets, &paths, &self.aliases, &self.options);
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(self.build_router(paths))
+    }
+
     /// Create an axum `Router` instance that will serve the included static assets
+    /// Panics when routes collide, other problems found by
+    /// [`MemoryServe::try_into_router`] are logged.
     /// Caution! This method leaks memory. It should only be called once (at startup).
     pub fn into_router<S>(self) -> axum::Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let paths = self.asset_paths();
+
+        for error in validate::validate(self.assets, &paths, &self.aliases, &self.options) {
+            if error.is_fatal() {
+                panic!("invalid memory-serve configuration: {error}");
+            }
+
+            warn!("{error}");
+        }
+
+        self.build_router(paths)
+    }
+
+    /// The paths each asset is served and redirected on, in the order of the
+    /// assets. Caution! This leaks the computed paths.
+    fn asset_paths(&self) -> Vec<canonical::AssetPaths> {
+        self.assets
+            .iter()
+            .map(|asset| canonical::AssetPaths::new(asset.route, &self.options))
+            .collect()
+    }
+
+    /// Create the router for the validated configuration, using the paths
+    /// computed by [`MemoryServe::asset_paths`].
+    fn build_router<S>(self, paths: Vec<canonical::AssetPaths>) -> axum::Router<S>
     where
         S: Clone + Send + Sync + 'static,
     {
         let mut router = axum::Router::new();
-        let options = Box::leak(Box::new(self.options));
+        let options: &'static options::ServeOptions = Box::leak(Box::new(self.options));
+        let assets: HashMap<_, _> = self
+            .assets
+            .iter()
+            .map(|asset| (asset.route, ServedAsset::new(asset, options)))
+            .collect();
+        let site: &'static Site = Box::leak(Box::new(Site {
+            options,
+            error_pages: ErrorPages::new(options, assets.values().copied()),
+            assets,
+            redirects: self.redirects.leak(),
+            router: OnceLock::new(),
+        }));
 
-        for asset in self.assets {
-            let (uncompressed_bytes, brotli_bytes, gzip_bytes) = asset.leak_bytes(options);
+        for (asset, paths) in self.assets.iter().zip(paths) {
+            let served = site.assets[asset.route];
+            let (uncompressed_bytes, brotli_bytes) = (served.bytes, served.brotli_bytes);
 
-            if !uncompressed_bytes.is_empty() {
+            if asset.bytes.is_some() {
                 if asset.is_compressed {
                     info!(
                         "serving {} {} -> {} bytes (compressed)",
@@ -154,51 +629,40 @@ impl MemoryServe {
                 info!("serving {} (dynamically)", asset.route);
             }
 
-            let handler = |headers: HeaderMap| {
-                ready(asset.handler(
-                    &headers,
-                    StatusCode::OK,
-                    uncompressed_bytes,
-                    brotli_bytes,
-                    gzip_bytes,
-                    options,
-                ))
+            let handler = move |uri: Uri, headers: HeaderMap| async move {
+                served
+                    .handler(uri.path(), &headers, StatusCode::OK, options)
+                    .await
             };
 
             if Some(asset.route) == options.fallback {
                 info!("serving {} as fallback", asset.route);
-
-                router = router.fallback(|headers: HeaderMap| {
-                    ready(asset.handler(
-                        &headers,
-                        options.fallback_status,
-                        uncompressed_bytes,
-                        brotli_bytes,
-                        gzip_bytes,
-                        options,
-                    ))
-                });
             }
 
-            if let Some(index) = options.index_file {
-                if asset.route == index {
-                    info!("serving {} as index on /", asset.route);
+            for (prefix, route) in &options.spa_fallbacks {
+                if *route == asset.route {
+                    info!("serving {} as SPA fallback on {}", asset.route, prefix);
+                }
+            }
 
-                    router = router.route("/", get(handler));
-                } else if options.index_on_subdirectories && asset.route.ends_with(index) {
-                    let path = &asset.route[..asset.route.len() - index.len()];
+            for path in paths.served {
+                if Some(path) == paths.index {
                     info!("serving {} as index on {}", asset.route, path);
-
-                    router = router.route(path, get(handler));
+                } else if path != asset.route {
+                    info!("serving {} on {}", asset.route, path);
                 }
+
+                router = router.route(path, get(handler));
             }
 
-            let path = if options.enable_clean_url && asset.route.ends_with(".html") {
-                &asset.route[..asset.route.len() - 5]
-            } else {
-                asset.route
-            };
-            router = router.route(path, get(handler));
+            for (from, to) in paths.redirects {
+                info!("redirecting {} to {}", from, to);
+
+                router = router.route(
+                    from,
+                    get(move |uri: Uri| ready(canonical::redirect_to_canonical(&uri, to))),
+                );
+            }
 
             // add all aliases that point to the asset route
             for (from, to) in self.aliases.iter() {
@@ -210,7 +674,20 @@ impl MemoryServe {
             }
         }
 
-        router
+        // rules and fallbacks only apply when no route matches
+        if site.has_fallback() {
+            router = router.fallback(move |request: Request| site.fallback(request));
+        }
+
+        if site.has_forced_redirects() {
+            router = router.layer(middleware::from_fn(move |request: Request, next: Next| {
+                site.forced_redirects(request, next)
+            }));
+        }
+
+        let _ = site.router.set(router.clone());
+
+        router.with_state(())
     }
 }
 
@@ -221,13 +698,20 @@ mod tests {
         body::Body,
         http::{
             self, HeaderMap, HeaderName, HeaderValue, Request, StatusCode,
-            header::{self, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH},
+            header::{
+                self, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH,
+                CONTENT_SECURITY_POLICY, LOCATION, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+                X_CONTENT_TYPE_OPTIONS,
+            },
         },
     };
     use std::sync::LazyLock;
     use tower::ServiceExt;
 
-    use crate::{self as memory_serve, Asset, CacheControl, MemoryServe};
+    use crate::{
+        self as memory_serve, Asset, CacheControl, CanonicalUrls, ContentSecurityPolicy,
+        DirectoryListing, MemoryServe, Redirect, RouteError, SecurityHeaders, TrailingSlash,
+    };
 
     static ASSETS: LazyLock<&'static [Asset]> =
         LazyLock::new(|| memory_serve::build::load_test_assets("./static"));
@@ -261,6 +745,22 @@ mod tests {
         headers.get(name).unwrap().to_str().unwrap()
     }
 
+    /// A plain text asset that is loaded dynamically from the path.
+    const fn test_asset(route: &'static str, path: &'static str) -> Asset {
+        Asset {
+            route,
+            path,
+            root: "",
+            etag: "",
+            integrity: "",
+            content_type: "text/plain",
+            bytes: None,
+            is_compressed: false,
+            should_compress: false,
+            nonce_offsets: &[],
+        }
+    }
+
     #[tokio::test]
     async fn test_load_assets() {
         let routes: Vec<&str> = ASSETS.iter().map(|a| a.route).collect();
@@ -282,18 +782,23 @@ mod tests {
         assert_eq!(
             content_types,
             [
-                "text/html",
+                "text/html; charset=utf-8",
                 "image/jpeg",
-                "text/css",
-                "text/javascript",
+                "text/css; charset=utf-8",
+                "text/javascript; charset=utf-8",
                 "image/svg+xml",
-                "text/html",
-                "text/html"
+                "text/html; charset=utf-8",
+                "text/html; charset=utf-8"
             ]
         );
         if cfg!(debug_assertions) && !cfg!(feature = "force-embed") {
             assert_eq!(etags, ["", "", "", "", "", "", ""]);
+            assert!(ASSETS.iter().all(|a| a.integrity().is_none()));
         } else {
+            assert_eq!(
+                ASSETS[6].integrity(),
+                Some("sha384-odkajEbDvRPOmSC5VEFEl1vPV3hAoDFTvOIx2dIq8Lyc3FUDOtWvJ3IBf7E+ezS8")
+            );
             assert_eq!(
                 etags,
                 [
@@ -437,6 +942,44 @@ mod tests {
         assert_eq!(code, 200);
     }
 
+    #[tokio::test]
+    async fn canonical_urls() {
+        let memory_router = test_load!()
+            .index_on_subdirectories(true)
+            .canonical_urls(
+                CanonicalUrls::new()
+                    .trailing_slash(TrailingSlash::Always)
+                    .strip_html_extension(true)
+                    .strip_index(true),
+            )
+            .into_router();
+
+        let (code, _) = get(memory_router.clone(), "/blog/", "accept", "*").await;
+        assert_eq!(code, 200);
+
+        let (code, headers) = get(memory_router.clone(), "/blog?page=2", "accept", "*").await;
+        assert_eq!(code, 308);
+        assert_eq!(get_header(&headers, &LOCATION), "/blog/?page=2");
+
+        let (code, headers) = get(memory_router.clone(), "/blog/index.html", "accept", "*").await;
+        assert_eq!(code, 308);
+        assert_eq!(get_header(&headers, &LOCATION), "/blog/");
+
+        let (code, headers) = get(memory_router.clone(), "/about.html", "accept", "*").await;
+        assert_eq!(code, 308);
+        assert_eq!(get_header(&headers, &LOCATION), "/about/");
+
+        let (code, _) = get(memory_router.clone(), "/about/", "accept", "*").await;
+        assert_eq!(code, 200);
+
+        let (code, headers) = get(memory_router.clone(), "/index.html", "accept", "*").await;
+        assert_eq!(code, 308);
+        assert_eq!(get_header(&headers, &LOCATION), "/");
+
+        let (code, _) = get(memory_router, "/assets/index.js", "accept", "*").await;
+        assert_eq!(code, 200);
+    }
+
     #[tokio::test]
     async fn fallback() {
         let memory_router = test_load!().into_router();
@@ -459,6 +1002,560 @@ mod tests {
         assert_eq!(length.parse::<i32>().unwrap(), 437);
     }
 
+    #[tokio::test]
+    async fn spa_fallback() {
+        let memory_router = test_load!()
+            .spa_fallback("/", "/index.html")
+            .spa_fallback("/blog", "/blog/index.html")
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/app/page", "accept", "text/html").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "437");
+
+        let (code, headers) = get(
+            memory_router.clone(),
+            "/blog/post",
+            "sec-fetch-mode",
+            "navigate",
+        )
+        .await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "431");
+
+        let (code, _) = get(memory_router.clone(), "/app/page", "sec-fetch-mode", "cors").await;
+        assert_eq!(code, 404);
+
+        let (code, _) = get(
+            memory_router.clone(),
+            "/assets/chunk-abc.js",
+            "accept",
+            "text/html",
+        )
+        .await;
+        assert_eq!(code, 404);
+
+        let memory_router = test_load!()
+            .spa_fallback("/app", "/index.html")
+            .fallback(Some("/about.html"))
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/app/page", "accept", "text/html").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "437");
+
+        let (code, headers) = get(memory_router, "/other/page", "accept", "text/html").await;
+        assert_eq!(code, 404);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "432");
+    }
+
+    #[tokio::test]
+    async fn error_pages() {
+        let memory_serve = test_load!()
+            .error_page(StatusCode::NOT_FOUND, "index.html")
+            .error_page(StatusCode::INTERNAL_SERVER_ERROR, "about.html");
+        let error_pages = memory_serve.error_pages();
+        let memory_router = memory_serve.into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/blog/missing", "accept", "*").await;
+        assert_eq!(code, 404);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "431");
+
+        let (code, headers) = get(memory_router.clone(), "/docs/missing", "accept", "*").await;
+        assert_eq!(code, 404);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "437");
+
+        // an error page is never revalidated, a matching ETag is still a 404
+        let (_, headers) = get(memory_router.clone(), "/blog/index.html", "accept", "*").await;
+        let etag = get_header(&headers, &header::ETAG);
+        let (code, headers) = get(
+            memory_router.clone(),
+            "/blog/missing",
+            "if-none-match",
+            etag,
+        )
+        .await;
+        assert_eq!(code, 404);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "431");
+
+        assert_eq!(
+            error_pages.find(StatusCode::NOT_FOUND, "/blog/a/b"),
+            Some("/blog/index.html")
+        );
+        assert_eq!(
+            error_pages.find(StatusCode::INTERNAL_SERVER_ERROR, "/blog/a"),
+            Some("/about.html")
+        );
+        assert_eq!(error_pages.find(StatusCode::FORBIDDEN, "/"), None);
+
+        let response = error_pages
+            .render(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "/blog/",
+                &HeaderMap::new(),
+            )
+            .await;
+        assert_eq!(response.status(), 500);
+        assert_eq!(get_header(response.headers(), &CONTENT_LENGTH), "432");
+    }
+
+    #[tokio::test]
+    async fn directory_listing() {
+        let memory_router = test_load!()
+            .index_file(None)
+            .directory_listing(
+                DirectoryListing::new()
+                    .template("{path}\n{entries}")
+                    .entry_template("{name} {size} {content_type}"),
+            )
+            .into_router();
+
+        let response = memory_router
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&body),
+            "/\nassets  \nblog  \nabout.html 432 text/html; charset=utf-8\nindex.html 437 text/html; charset=utf-8"
+        );
+
+        let response = memory_router
+            .clone()
+            .oneshot(
+                Request::get("/assets?sort=size&order=desc")
+                    .header("accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            get_header(response.headers(), &header::CONTENT_TYPE),
+            "application/json"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8_lossy(&body);
+        let names: Vec<&str> = body
+            .split(r#""name":""#)
+            .skip(1)
+            .filter_map(|s| s.split('"').next())
+            .collect();
+        assert_eq!(names, ["stars.svg", "index.css", "icon.jpg", "index.js"]);
+
+        let (code, _) = get(memory_router, "/missing/", "accept", "*").await;
+        assert_eq!(code, 404);
+    }
+
+    #[test]
+    fn get_asset() {
+        let memory_serve = test_load!()
+            .enable_brotli(true)
+            .add_alias("/home", "/index.html");
+
+        let asset = memory_serve.get("/home").unwrap();
+        assert_eq!(asset.route(), "/index.html");
+        assert_eq!(asset.content_type(), "text/html; charset=utf-8");
+        assert_eq!(asset.size(), 437);
+        assert!(asset.bytes().starts_with(b"<!doctype html>"));
+        assert_eq!(
+            asset.etag(),
+            "0639dc8aac157b58c74f65bbb026b2fd42bc81d9a0a64141df456fa23c214537"
+        );
+        assert_eq!(asset.encodings(), ["br", "identity"]);
+
+        let asset = memory_serve.get("/assets/icon.jpg").unwrap();
+        assert_eq!(asset.encodings(), ["identity"]);
+
+        assert!(memory_serve.get("/missing").is_none());
+        assert_eq!(memory_serve.iter().count(), 7);
+        assert_eq!(memory_serve.iter().map(|a| a.size()).sum::<usize>(), 6493);
+    }
+
+    #[tokio::test]
+    async fn respond() {
+        let memory_serve = test_load!()
+            .enable_gzip(true)
+            .add_alias("/home", "/index.html");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+
+        let response = memory_serve
+            .respond("/home", &headers)
+            .unwrap()
+            .status(StatusCode::FORBIDDEN)
+            .header(CACHE_CONTROL, HeaderValue::from_static("no-store"))
+            .into_response()
+            .await;
+        assert_eq!(response.status(), 403);
+        assert_eq!(get_header(response.headers(), &CONTENT_ENCODING), "gzip");
+        assert_eq!(get_header(response.headers(), &CACHE_CONTROL), "no-store");
+        assert_eq!(
+            get_header(response.headers(), &header::CONTENT_TYPE),
+            "text/html; charset=utf-8"
+        );
+
+        let response = memory_serve
+            .respond("/assets/index.js", &HeaderMap::new())
+            .unwrap()
+            .into_response()
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(get_header(response.headers(), &CONTENT_LENGTH), "20");
+
+        assert!(memory_serve.respond("/missing", &headers).is_none());
+
+        // the prepared bytes are reused
+        memory_serve.respond("/index.html", &headers).unwrap();
+        assert_eq!(memory_serve.prepared.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn merge() {
+        let memory_router = test_load!()
+            .index_file(None)
+            .add_alias("/home", "/index.html")
+            .merge(
+                test_load!()
+                    .add_alias("/start", "/about.html")
+                    .header("/assets/*", "x-shared", "1")
+                    .header("*", "x-all", "1")
+                    .header("text/javascript", "x-script", "1")
+                    .prefix("/shared/"),
+            )
+            .merge(test_load!().add_alias("/about", "/about.html"))
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/home", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "437");
+
+        let (code, headers) = get(memory_router.clone(), "/about", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "432");
+
+        let (code, headers) = get(memory_router.clone(), "/shared/start", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "432");
+
+        let (code, headers) = get(
+            memory_router.clone(),
+            "/shared/assets/index.js",
+            "accept",
+            "*",
+        )
+        .await;
+        assert_eq!(code, 200);
+        assert_eq!(
+            get_header(&headers, &HeaderName::from_static("x-shared")),
+            "1"
+        );
+        assert_eq!(get_header(&headers, &HeaderName::from_static("x-all")), "1");
+        assert_eq!(
+            get_header(&headers, &HeaderName::from_static("x-script")),
+            "1"
+        );
+
+        // header rules of merged instances do not apply to other assets
+        let (_, headers) = get(memory_router.clone(), "/assets/index.js", "accept", "*").await;
+        assert!(headers.get("x-shared").is_none());
+        assert!(headers.get("x-all").is_none());
+        assert!(headers.get("x-script").is_none());
+    }
+
+    #[test]
+    fn route_errors() {
+        assert!(test_load!().try_into_router::<()>().is_ok());
+
+        let errors = test_load!()
+            .index_on_subdirectories(true)
+            .add_alias("/blog", "/about.html")
+            .add_alias("/old", "/missing.html")
+            .try_into_router::<()>()
+            .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                RouteError::Collision {
+                    path: "/blog".to_string(),
+                    first: "asset /blog/index.html".to_string(),
+                    second: "alias to /about.html".to_string(),
+                },
+                RouteError::MissingAliasTarget {
+                    from: "/old",
+                    to: "/missing.html"
+                },
+            ]
+        );
+
+        let errors = test_load!()
+            .index_file(Some("/home.html"))
+            .fallback(Some("/404.html"))
+            .spa_fallback("/app", "/app.html")
+            .try_into_router::<()>()
+            .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                RouteError::MissingIndex("/home.html"),
+                RouteError::MissingFallback("/404.html"),
+                RouteError::MissingSpaFallback {
+                    prefix: "/app",
+                    route: "/app.html"
+                },
+            ]
+        );
+
+        // the default index file is optional
+        static SCRIPTS: &[Asset] = &[Asset {
+            content_type: "text/javascript",
+            bytes: Some(&[]),
+            ..test_asset("/app.js", "/nonexistent/app.js")
+        }];
+
+        assert!(MemoryServe::new(SCRIPTS).try_into_router::<()>().is_ok());
+        assert_eq!(
+            MemoryServe::new(SCRIPTS)
+                .index_file(Some("/index.html"))
+                .try_into_router::<()>()
+                .unwrap_err(),
+            [RouteError::MissingIndex("/index.html")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "path /blog/index.html is used by both")]
+    fn route_collision() {
+        let _ = test_load!()
+            .add_alias("/blog/index.html", "/about.html")
+            .into_router::<()>();
+    }
+
+    #[tokio::test]
+    async fn empty_file() {
+        static ASSETS: &[Asset] = &[Asset {
+            etag: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            bytes: Some(&[]),
+            ..test_asset("/CNAME", "/nonexistent/CNAME")
+        }];
+
+        let memory_router = MemoryServe::new(ASSETS).into_router();
+        let (code, headers) = get(memory_router, "/CNAME", "accept", "*").await;
+
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "0");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dynamic_guards() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("memory-serve-guard-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        let base = base.canonicalize().unwrap();
+        let root = base.join("static");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(base.join("private")).unwrap();
+        std::fs::write(root.join("page.txt"), "page").unwrap();
+        std::fs::write(root.join("docs/notes.txt"), "notes").unwrap();
+        std::fs::write(base.join("private/secret.txt"), "secret").unwrap();
+        std::fs::write(base.join("private/notes.txt"), "private").unwrap();
+
+        let leak = |path: std::path::PathBuf| -> &'static str {
+            path.to_string_lossy().into_owned().leak()
+        };
+        let root_str = leak(root.clone());
+        let asset = |route: &'static str, path: &str| Asset {
+            root: root_str,
+            ..test_asset(route, leak(root.join(path)))
+        };
+        let assets: &'static [Asset] = vec![
+            asset("/page.txt", "page.txt"),
+            asset("/link.txt", "link.txt"),
+            asset("/docs/notes.txt", "docs/notes.txt"),
+            asset("/docs", "docs"),
+            asset("/missing.txt", "missing.txt"),
+            // the resolved target of a symlink followed at build time
+            Asset {
+                path: leak(base.join("private/notes.txt")),
+                ..asset("/target.txt", "")
+            },
+            Asset {
+                path: leak(base.join("docs/../private/notes.txt")),
+                ..asset("/parent.txt", "")
+            },
+        ]
+        .leak();
+
+        // replace files after the build
+        symlink(base.join("private/secret.txt"), root.join("link.txt")).unwrap();
+        std::fs::rename(root.join("docs"), base.join("docs")).unwrap();
+        symlink(base.join("private"), root.join("docs")).unwrap();
+
+        let memory_router = MemoryServe::new(assets).into_router();
+        let status = |path: &'static str| {
+            let router = memory_router.clone();
+
+            async move { get(router, path, "accept", "*").await.0 }
+        };
+
+        assert_eq!(status("/page.txt").await, 200);
+        assert_eq!(status("/link.txt").await, 403);
+        assert_eq!(status("/docs/notes.txt").await, 403);
+        assert_eq!(status("/docs").await, 403);
+        assert_eq!(status("/missing.txt").await, 404);
+        assert_eq!(status("/target.txt").await, 200);
+        assert_eq!(status("/parent.txt").await, 403);
+
+        let memory_router = MemoryServe::new(assets)
+            .max_dynamic_size(Some(3))
+            .into_router();
+        let (code, _) = get(memory_router, "/page.txt", "accept", "*").await;
+        assert_eq!(code, 403);
+
+        // streamed files are not limited
+        let memory_router = MemoryServe::new(assets)
+            .max_dynamic_size(Some(3))
+            .stream_threshold(3)
+            .into_router();
+        let (code, headers) = get(memory_router, "/page.txt", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "4");
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn asset_pack() {
+        let base =
+            std::env::temp_dir().join(format!("memory-serve-load-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        let path = base.join("memory_serve.pack");
+        let pack = crate::pack::write_pack(&[("default", "/index.html", b"<p>packed</p>")]);
+        std::fs::write(&path, &pack).unwrap();
+
+        let asset_pack: &'static memory_serve::AssetPack =
+            Box::leak(Box::new(memory_serve::AssetPack {
+                file_name: "memory_serve.pack",
+                hash: sha256::digest(&pack).leak(),
+            }));
+        crate::pack::load_path(&path, asset_pack).unwrap();
+
+        static ASSETS: &[Asset] = &[Asset {
+            etag: "etag",
+            content_type: "text/html",
+            ..test_asset("/index.html", "/index.html")
+        }];
+        let directory: &'static memory_serve::AssetDirectory =
+            Box::leak(Box::new(memory_serve::AssetDirectory {
+                name: "default",
+                assets: ASSETS,
+                redirects: &[],
+                headers: &[],
+                pack: Some(asset_pack),
+            }));
+        let memory_serve = MemoryServe::from_directory(directory);
+        assert_eq!(
+            memory_serve.get("/index.html").unwrap().bytes(),
+            b"<p>packed</p>"
+        );
+
+        let (code, headers) = get(memory_serve.into_router(), "/", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "13");
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dynamic_not_modified() {
+        static ASSETS: &[Asset] = &[Asset {
+            content_type: "text/html",
+            should_compress: true,
+            ..test_asset("/index.html", "./static/index.html")
+        }];
+
+        let memory_router = MemoryServe::new(ASSETS).into_router();
+        let (code, headers) = get(memory_router.clone(), "/index.html", "accept", "*").await;
+        let etag = get_header(&headers, &header::ETAG).to_string();
+        let last_modified = get_header(&headers, &header::LAST_MODIFIED).to_string();
+        assert_eq!(code, 200);
+
+        let (code, headers) =
+            get(memory_router.clone(), "/index.html", "if-none-match", &etag).await;
+        assert_eq!(code, 304);
+        assert_eq!(get_header(&headers, &header::ETAG), etag);
+
+        let (code, _) = get(
+            memory_router.clone(),
+            "/index.html",
+            "if-modified-since",
+            &last_modified,
+        )
+        .await;
+        assert_eq!(code, 304);
+
+        let (code, _) = get(
+            memory_router,
+            "/index.html",
+            "if-modified-since",
+            "Thu, 01 Jan 1970 00:00:00 GMT",
+        )
+        .await;
+        assert_eq!(code, 200);
+    }
+
+    #[tokio::test]
+    async fn dynamic_stream() {
+        static ASSETS: &[Asset] = &[Asset {
+            content_type: "image/svg+xml",
+            should_compress: true,
+            ..test_asset("/stars.svg", "./static/assets/stars.svg")
+        }];
+
+        let bytes = std::fs::read("./static/assets/stars.svg").unwrap();
+        let memory_router = MemoryServe::new(ASSETS)
+            .enable_brotli(true)
+            .stream_threshold(1024)
+            .into_router();
+
+        let response = memory_router
+            .clone()
+            .oneshot(Request::get("/stars.svg").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let etag = get_header(response.headers(), &header::ETAG).to_string();
+        assert!(etag.starts_with("W/"));
+        assert_eq!(
+            get_header(response.headers(), &header::CONTENT_LENGTH),
+            bytes.len().to_string()
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, bytes);
+
+        let (code, headers) =
+            get(memory_router.clone(), "/stars.svg", "accept-encoding", "br").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &header::CONTENT_ENCODING), "br");
+        assert!(!headers.contains_key(header::CONTENT_LENGTH));
+
+        let (code, _) = get(memory_router, "/stars.svg", "if-none-match", &etag).await;
+        assert_eq!(code, 304);
+    }
+
     #[tokio::test]
     async fn cache_control() {
         async fn check_cache_control(cache_control: CacheControl, expected: &str) {
@@ -524,6 +1621,246 @@ mod tests {
         .await;
     }
 
+    const CROSS_ORIGIN_RESOURCE_POLICY: HeaderName =
+        HeaderName::from_static("cross-origin-resource-policy");
+
+    #[tokio::test]
+    async fn custom_headers() {
+        let memory_router = test_load!()
+            .header("*", "X-Content-Type-Options", "nosniff")
+            .header("text/html", "Content-Security-Policy", "default-src 'self'")
+            .header("/assets/*", "Cross-Origin-Resource-Policy", "same-origin")
+            .header("/assets/icon.jpg", "Content-Disposition", "attachment")
+            .header("image/*", "Cache-Control", "no-store")
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/index.html", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(
+            get_header(&headers, &CONTENT_SECURITY_POLICY),
+            "default-src 'self'"
+        );
+        assert!(!headers.contains_key(CROSS_ORIGIN_RESOURCE_POLICY));
+        assert!(!headers.contains_key(CONTENT_DISPOSITION));
+
+        let (code, headers) = get(memory_router.clone(), "/assets/icon.jpg", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(
+            get_header(&headers, &CROSS_ORIGIN_RESOURCE_POLICY),
+            "same-origin"
+        );
+        assert_eq!(get_header(&headers, &CONTENT_DISPOSITION), "attachment");
+        assert_eq!(get_header(&headers, &CACHE_CONTROL), "no-store");
+        assert!(!headers.contains_key(CONTENT_SECURITY_POLICY));
+
+        let (code, headers) = get(memory_router, "/assets/index.css", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(
+            get_header(&headers, &CROSS_ORIGIN_RESOURCE_POLICY),
+            "same-origin"
+        );
+        assert_eq!(
+            get_header(&headers, &CACHE_CONTROL),
+            CacheControl::Medium.as_header().1.to_str().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn headers_file() {
+        let rules = crate::custom_headers::parse_headers_file(
+            "# comment\n/*\n  X-Frame-Options: DENY\n/assets/*\n  Cache-Control: no-cache\n  X-Robots-Tag: noindex\n/about\n  X-Robots-Tag: none\n",
+            |message| panic!("{message}"),
+        );
+        let headers: Vec<(&'static str, &'static str, &'static str)> = rules
+            .into_iter()
+            .map(|(pattern, name, value)| (&*pattern.leak(), &*name.leak(), &*value.leak()))
+            .collect();
+        let directory: &'static memory_serve::AssetDirectory =
+            Box::leak(Box::new(memory_serve::AssetDirectory {
+                name: "default",
+                assets: *ASSETS,
+                redirects: &[],
+                headers: headers.leak(),
+                pack: None,
+            }));
+        let memory_router = MemoryServe::from_directory(directory)
+            .canonical_urls(CanonicalUrls::new().strip_html_extension(true))
+            .into_router();
+        let x_frame_options = HeaderName::from_static("x-frame-options");
+        let x_robots_tag = HeaderName::from_static("x-robots-tag");
+
+        let (code, headers) = get(memory_router.clone(), "/", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &x_frame_options), "DENY");
+        assert!(!headers.contains_key(&x_robots_tag));
+
+        let (code, headers) = get(memory_router.clone(), "/assets/index.css", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &x_frame_options), "DENY");
+        assert_eq!(get_header(&headers, &x_robots_tag), "noindex");
+        assert_eq!(get_header(&headers, &CACHE_CONTROL), "no-cache");
+
+        // patterns match the path the asset is served on
+        let (code, headers) = get(memory_router, "/about", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &x_robots_tag), "none");
+    }
+
+    #[tokio::test]
+    async fn security_headers() {
+        let memory_router = test_load!()
+            .security_headers(SecurityHeaders::strict())
+            .header("text/html", "Referrer-Policy", "same-origin")
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/index.html", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(get_header(&headers, &REFERRER_POLICY), "same-origin");
+        assert_eq!(
+            get_header(&headers, &CONTENT_SECURITY_POLICY),
+            ContentSecurityPolicy::strict().to_string()
+        );
+        assert!(headers.contains_key(STRICT_TRANSPORT_SECURITY));
+        assert!(headers.contains_key("cross-origin-embedder-policy"));
+
+        let (code, headers) = get(memory_router, "/assets/icon.jpg", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(
+            get_header(&headers, &CROSS_ORIGIN_RESOURCE_POLICY),
+            "same-origin"
+        );
+        assert!(headers.contains_key(STRICT_TRANSPORT_SECURITY));
+        assert!(!headers.contains_key(CONTENT_SECURITY_POLICY));
+        assert!(!headers.contains_key(REFERRER_POLICY));
+
+        let memory_router = test_load!()
+            .security_headers(
+                SecurityHeaders::spa()
+                    .content_security_policy(Some(
+                        ContentSecurityPolicy::spa().connect_src(["https://api.example.com"]),
+                    ))
+                    .strict_transport_security(None),
+            )
+            .into_router();
+
+        let (_, headers) = get(memory_router, "/index.html", "accept", "*").await;
+        assert!(
+            get_header(&headers, &CONTENT_SECURITY_POLICY)
+                .contains("connect-src https://api.example.com")
+        );
+        assert!(!headers.contains_key(STRICT_TRANSPORT_SECURITY));
+        assert!(!headers.contains_key("cross-origin-embedder-policy"));
+    }
+
+    #[tokio::test]
+    async fn csp_nonce() {
+        let memory_router = test_load!()
+            .enable_csp_nonce(true)
+            .enable_brotli(true)
+            .security_headers(SecurityHeaders::strict())
+            .into_router();
+
+        let response = memory_router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/index.html")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let headers = response.headers().to_owned();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        let policy = get_header(&headers, &CONTENT_SECURITY_POLICY);
+        let nonce = policy
+            .split("'nonce-")
+            .nth(1)
+            .and_then(|s| s.split('\'').next())
+            .unwrap();
+
+        assert!(policy.starts_with("default-src 'none'; script-src 'self' 'nonce-"));
+        assert!(body.contains(&format!("<script nonce=\"{nonce}\"")));
+        assert!(!headers.contains_key(header::ETAG));
+        assert_eq!(get_header(&headers, &CACHE_CONTROL), "no-store");
+        assert_eq!(
+            get_header(&headers, &CONTENT_LENGTH)
+                .parse::<usize>()
+                .unwrap(),
+            body.len()
+        );
+
+        let (code, headers) = get(
+            memory_router.clone(),
+            "/index.html",
+            "accept-encoding",
+            "br",
+        )
+        .await;
+        let other_policy = get_header(&headers, &CONTENT_SECURITY_POLICY);
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_ENCODING), "br");
+        assert_ne!(policy, other_policy);
+
+        let (code, headers) = get(memory_router, "/assets/index.js", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert!(headers.contains_key(header::ETAG));
+    }
+
+    #[tokio::test]
+    async fn redirects() {
+        let memory_router = test_load!()
+            .redirects(
+                "
+                /home       /                 301
+                /old/*      /blog/:splat      302
+                /app/*      /index.html       200
+                /index.html /about.html       302!
+                /missing/*  /not-found.html   200
+                ",
+            )
+            .add_redirect(Redirect::new("/p", "/p/:id", 307).query(&[("id", ":id")]))
+            .into_router();
+
+        let (code, headers) = get(memory_router.clone(), "/home", "accept", "*").await;
+        assert_eq!(code, 301);
+        assert_eq!(get_header(&headers, &LOCATION), "/");
+
+        let (code, headers) =
+            get(memory_router.clone(), "/old/index.html?x=1", "accept", "*").await;
+        assert_eq!(code, 302);
+        assert_eq!(get_header(&headers, &LOCATION), "/blog/index.html?x=1");
+
+        let (code, headers) = get(memory_router.clone(), "/app/some/page", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(get_header(&headers, &CONTENT_LENGTH), "437");
+
+        let (code, headers) = get(memory_router.clone(), "/index.html", "accept", "*").await;
+        assert_eq!(code, 302);
+        assert_eq!(get_header(&headers, &LOCATION), "/about.html");
+
+        let (code, _) = get(memory_router.clone(), "/about.html", "accept", "*").await;
+        assert_eq!(code, 200);
+
+        let (code, _) = get(memory_router.clone(), "/missing/page", "accept", "*").await;
+        assert_eq!(code, 404);
+
+        let (code, headers) = get(memory_router.clone(), "/p?id=42", "accept", "*").await;
+        assert_eq!(code, 307);
+        assert_eq!(get_header(&headers, &LOCATION), "/p/42");
+
+        let (code, _) = get(memory_router, "/p", "accept", "*").await;
+        assert_eq!(code, 404);
+    }
+
     #[tokio::test]
     async fn aliases() {
         let memory_router = test_load!()
@@ -542,4 +1879,58 @@ mod tests {
         let (code, _) = get(memory_router.clone(), "/barfoo", "accept", "*").await;
         assert_eq!(code, 404);
     }
+
+    #[cfg(feature = "archive")]
+    #[tokio::test]
+    async fn from_archive() {
+        let base =
+            std::env::temp_dir().join(format!("memory-serve-from-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+
+        let mut tar = tar::Builder::new(std::fs::File::create(base.join("dist.tar")).unwrap());
+        for (path, contents) in [
+            ("index.html", &b"<p>archive</p>"[..]),
+            ("assets/index.js", b"let a = 1;"),
+            ("_redirects", b"/old /index.html 301"),
+            ("_headers", b"/assets/*\n  X-Archive: yes"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, contents).unwrap();
+        }
+        tar.finish().unwrap();
+
+        let memory_router = MemoryServe::from_archive(base.join("dist.tar"))
+            .unwrap()
+            .index_file(Some("/index.html"))
+            .into_router();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        let (code, headers) = get(memory_router.clone(), "/", "accept", "*").await;
+        assert_eq!(code, 200);
+        assert_eq!(
+            get_header(&headers, &header::CONTENT_TYPE),
+            "text/html; charset=utf-8"
+        );
+
+        let (code, headers) = get(
+            memory_router.clone(),
+            "/assets/index.js",
+            "accept-encoding",
+            "br",
+        )
+        .await;
+        assert_eq!(code, 200);
+        assert_eq!(
+            get_header(&headers, &HeaderName::from_static("x-archive")),
+            "yes"
+        );
+
+        let (code, headers) = get(memory_router.clone(), "/old", "accept", "*").await;
+        assert_eq!(code, 301);
+        assert_eq!(get_header(&headers, &LOCATION), "/index.html");
+
+        assert!(MemoryServe::from_archive(base.join("missing.tar")).is_err());
+    }
 }
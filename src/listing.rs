@@ -0,0 +1,376 @@
+use axum::{
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{asset::ServedAsset, options::ServeOptions};
+
+/// Default page template, see [`DirectoryListing::template`].
+const DEFAULT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Index of {path}</title>
+</head>
+<body>
+<h1>Index of {path}</h1>
+<table>
+<thead><tr><th><a href="?sort=name">Name</a></th><th><a href="?sort=size&amp;order=desc">Size</a></th><th><a href="?sort=type">Type</a></th><th>ETag</th></tr></thead>
+<tbody>
+{entries}
+</tbody>
+</table>
+</body>
+</html>
+"#;
+
+/// Default entry template, see [`DirectoryListing::entry_template`].
+const DEFAULT_ENTRY_TEMPLATE: &str = r#"<tr><td><a href="{href}">{name}</a></td><td>{size}</td><td>{content_type}</td><td>{etag}</td></tr>"#;
+
+/// Column to sort a directory listing on, subdirectories are always listed first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListingSort {
+    /// Sort on the file name (default).
+    #[default]
+    Name,
+    /// Sort on the uncompressed file size.
+    Size,
+    /// Sort on the MIME type.
+    ContentType,
+}
+
+impl ListingSort {
+    /// Parse the value of the `sort` query parameter.
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            "type" => Some(Self::ContentType),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration of the generated listings for directories without index
+/// file. The listing is served as HTML, or as JSON when the client accepts
+/// `application/json`. The `sort` (`name`, `size` or `type`) and `order`
+/// (`asc` or `desc`) query parameters override the configured sorting.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectoryListing {
+    sort: ListingSort,
+    descending: bool,
+    template: &'static str,
+    entry_template: &'static str,
+}
+
+impl Default for DirectoryListing {
+    fn default() -> Self {
+        Self {
+            sort: ListingSort::Name,
+            descending: false,
+            template: DEFAULT_TEMPLATE,
+            entry_template: DEFAULT_ENTRY_TEMPLATE,
+        }
+    }
+}
+
+impl DirectoryListing {
+    /// Create a listing configuration with the default sorting and templates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which column to sort entries on by default.
+    pub fn sort(mut self, sort: ListingSort) -> Self {
+        self.sort = sort;
+
+        self
+    }
+
+    /// Whether to sort entries in descending order by default.
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+
+        self
+    }
+
+    /// HTML template of the listing page, `{path}` is replaced by the
+    /// directory path and `{entries}` by the rendered entries.
+    pub fn template(mut self, template: &'static str) -> Self {
+        self.template = template;
+
+        self
+    }
+
+    /// HTML template of a single entry, the placeholders `{href}`, `{name}`,
+    /// `{size}`, `{content_type}` and `{etag}` are replaced by the (escaped)
+    /// values of the entry. Subdirectories have an empty size, content type
+    /// and etag.
+    pub fn entry_template(mut self, entry_template: &'static str) -> Self {
+        self.entry_template = entry_template;
+
+        self
+    }
+
+    /// Render the listing of the directory, if any asset is located in it.
+    pub(crate) fn render(
+        &self,
+        assets: &HashMap<&'static str, ServedAsset>,
+        options: &ServeOptions,
+        path: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+    ) -> Option<Response> {
+        let directory = format!("{}/", path.trim_end_matches('/'));
+        let mut entries = list_entries(assets, &directory);
+
+        if entries.is_empty() {
+            return None;
+        }
+
+        let (sort, descending) = self.sorting(query);
+        sort_entries(&mut entries, sort, descending);
+
+        let accepts_json = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.contains("application/json"));
+
+        let (content_type, body) = if accepts_json {
+            ("application/json", to_json(&directory, &entries))
+        } else {
+            (
+                "text/html; charset=utf-8",
+                self.render_html(&directory, &entries),
+            )
+        };
+
+        Some(
+            (
+                StatusCode::OK,
+                [
+                    (CONTENT_TYPE, HeaderValue::from_static(content_type)),
+                    options.html_cache_control.as_header(),
+                ],
+                body,
+            )
+                .into_response(),
+        )
+    }
+
+    /// The sorting of the configuration, overridden by the query parameters.
+    fn sorting(&self, query: Option<&str>) -> (ListingSort, bool) {
+        let (mut sort, mut descending) = (self.sort, self.descending);
+
+        for (key, value) in query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+        {
+            match key {
+                "sort" => sort = ListingSort::parse(value).unwrap_or(sort),
+                "order" => descending = value == "desc",
+                _ => {}
+            }
+        }
+
+        (sort, descending)
+    }
+
+    /// Render the entries using the templates.
+    fn render_html(&self, directory: &str, entries: &[Entry]) -> String {
+        let rows: Vec<String> = entries
+            .iter()
+            .map(|entry| {
+                let size = entry.size.map(|s| s.to_string()).unwrap_or_default();
+
+                self.entry_template
+                    .replace("{href}", &escape_html(&entry.href))
+                    .replace("{name}", &escape_html(&entry.name))
+                    .replace("{size}", &size)
+                    .replace("{content_type}", &escape_html(entry.content_type))
+                    .replace("{etag}", &escape_html(entry.etag))
+            })
+            .collect();
+
+        self.template
+            .replace("{path}", &escape_html(directory))
+            .replace("{entries}", &rows.join("\n"))
+    }
+}
+
+/// A file or subdirectory in a directory listing.
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    href: String,
+    /// `None` for a subdirectory.
+    size: Option<u64>,
+    content_type: &'static str,
+    etag: &'static str,
+}
+
+/// Collect the files and subdirectories directly in the directory.
+fn list_entries(assets: &HashMap<&'static str, ServedAsset>, directory: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for (route, served) in assets {
+        let Some(rest) = route.strip_prefix(directory) else {
+            continue;
+        };
+
+        match rest.split_once('/') {
+            Some((name, _)) => {
+                if !entries.iter().any(|e| e.size.is_none() && e.name == name) {
+                    entries.push(Entry {
+                        name: name.to_string(),
+                        href: format!("{directory}{name}/"),
+                        size: None,
+                        content_type: "",
+                        etag: "",
+                    });
+                }
+            }
+            None => entries.push(Entry {
+                name: rest.to_string(),
+                href: route.to_string(),
+                size: Some(size(served)),
+                content_type: served.asset.content_type,
+                etag: served.asset.etag,
+            }),
+        }
+    }
+
+    entries
+}
+
+/// The uncompressed size of an asset, read from the file metadata when the
+/// asset is loaded dynamically.
+fn size(served: &ServedAsset) -> u64 {
+    if served.asset.bytes.is_none() {
+        std::fs::metadata(served.asset.path).map_or(0, |m| m.len())
+    } else {
+        served.bytes.len() as u64
+    }
+}
+
+/// Sort entries, subdirectories first.
+fn sort_entries(entries: &mut [Entry], sort: ListingSort, descending: bool) {
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            ListingSort::Name => Ordering::Equal,
+            ListingSort::Size => a.size.cmp(&b.size),
+            ListingSort::ContentType => a.content_type.cmp(b.content_type),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+
+        b.size.is_none().cmp(&a.size.is_none()).then(ordering)
+    });
+}
+
+/// Serialize the listing as a JSON object.
+fn to_json(directory: &str, entries: &[Entry]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|entry| match entry.size {
+            Some(size) => format!(
+                r#"{{"name":"{}","href":"{}","type":"file","size":{size},"content_type":"{}","etag":"{}"}}"#,
+                escape_json(&entry.name),
+                escape_json(&entry.href),
+                escape_json(entry.content_type),
+                escape_json(entry.etag),
+            ),
+            None => format!(
+                r#"{{"name":"{}","href":"{}","type":"directory"}}"#,
+                escape_json(&entry.name),
+                escape_json(&entry.href),
+            ),
+        })
+        .collect();
+
+    format!(
+        r#"{{"path":"{}","entries":[{}]}}"#,
+        escape_json(directory),
+        entries.join(",")
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn escape_json(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            c if c.is_control() => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, ListingSort, escape_json, sort_entries, to_json};
+
+    fn entry(name: &str, size: Option<u64>, content_type: &'static str) -> Entry {
+        Entry {
+            name: name.to_string(),
+            href: format!("/{name}"),
+            size,
+            content_type,
+            etag: "",
+        }
+    }
+
+    #[test]
+    fn sorting() {
+        let mut entries = vec![
+            entry("b.txt", Some(1), "text/plain"),
+            entry("a.css", Some(2), "text/css"),
+            entry("z", None, ""),
+        ];
+
+        sort_entries(&mut entries, ListingSort::Name, false);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["z", "a.css", "b.txt"]);
+
+        sort_entries(&mut entries, ListingSort::Size, true);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["z", "a.css", "b.txt"]);
+
+        sort_entries(&mut entries, ListingSort::ContentType, true);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["z", "b.txt", "a.css"]);
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(escape_json("a\"b\\\n"), "a\\\"b\\\\\\u000a");
+        assert_eq!(
+            to_json(
+                "/",
+                &[entry("a", None, ""), entry("b", Some(3), "text/plain")]
+            ),
+            r#"{"path":"/","entries":[{"name":"a","href":"/a","type":"directory"},{"name":"b","href":"/b","type":"file","size":3,"content_type":"text/plain","etag":""}]}"#
+        );
+    }
+}
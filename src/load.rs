@@ -1,37 +1,82 @@
 #[allow(unused)]
 use crate as memory_serve;
+use crate::{Asset, Redirect, pack::AssetPack};
+
+/// The assets and rules of a named directory, generated by the build step.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct AssetDirectory {
+    pub name: &'static str,
+    pub assets: &'static [Asset],
+    pub redirects: &'static [Redirect],
+    pub headers: &'static [(&'static str, &'static str, &'static str)],
+    pub pack: Option<&'static AssetPack>,
+}
+
+/// Compare strings in a constant context, used by the generated code to look
+/// up the index of a named directory.
+#[doc(hidden)]
+pub const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut index = 0;
+
+    while index < a.len() {
+        if a[index] != b[index] {
+            return false;
+        }
+
+        index += 1;
+    }
+
+    true
+}
 
 /// Include the generated asset manifest and construct a `MemoryServe` struct.
+/// The name of a directory must be a constant, an unknown name results in a
+/// compile error listing the valid names. To select a directory at runtime,
+/// match the name and call `load!` with a constant in each arm. Multiple names merge the
+/// directories, earlier directories take precedence, see `MemoryServe::merge`.
 #[macro_export]
 macro_rules! load {
     () => {{
-        use memory_serve::{Asset, MemoryServe};
+        use memory_serve::{AssetDirectory, MemoryServe};
 
-        let assets: &[(&str, &[Asset])] =
+        let directories: &[AssetDirectory] =
             include!(concat!(env!("OUT_DIR"), "/memory_serve_assets.rs"));
 
-        if assets.is_empty() {
+        if directories.is_empty() {
             panic!("No assets found, did you call a load_directory* function from your build.rs?");
         }
 
-        MemoryServe::new(assets[0].1)
+        MemoryServe::from_directory(&directories[0])
     }};
     ($title:expr) => {{
-        use memory_serve::{Asset, MemoryServe};
+        use memory_serve::{AssetDirectory, MemoryServe};
 
-        let assets: &[(&str, &[Asset])] =
-            include!(concat!(env!("OUT_DIR"), "/memory_serve_assets.rs"));
-
-        let selected_assets = assets
-            .into_iter()
-            .find(|(n, _)| *n == $title)
-            .map(|(_, a)| *a)
-            .unwrap_or_default();
+        const INDEX: usize = include!(concat!(env!("OUT_DIR"), "/memory_serve_names.rs"))($title);
 
-        if selected_assets.is_empty() {
-            panic!("No assets found, did you call a load_directory* function from your build.rs?");
-        }
+        let directories: &[AssetDirectory] =
+            include!(concat!(env!("OUT_DIR"), "/memory_serve_assets.rs"));
 
-        MemoryServe::new(selected_assets)
+        MemoryServe::from_directory(&directories[INDEX])
     }};
+    ($title:expr, $($other:expr),+ $(,)?) => {{
+        memory_serve::load!($title)$(.merge(memory_serve::load!($other)))+
+    }};
+}
+
+/// Include the generated route constants. For every named directory a module
+/// is generated, containing a constant with the route and the subresource
+/// integrity of each asset, e.g. `default::INDEX_HTML` and
+/// `default::INDEX_HTML_INTEGRITY`.
+#[macro_export]
+macro_rules! routes {
+    () => {
+        include!(concat!(env!("OUT_DIR"), "/memory_serve_routes.rs"));
+    };
 }
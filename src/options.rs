@@ -1,10 +1,21 @@
 use axum::http::StatusCode;
 
-use crate::CacheControl;
+use crate::{
+    CacheControl, CanonicalUrls, DirectoryListing, SecurityHeaders, custom_headers::HeaderRule,
+};
 
 /// Minimum file size (in bytes) to consider compression
 pub(crate) const MIN_COMPRESS_SIZE: u64 = 128;
 
+/// Maximum file size (in bytes) served in dynamic mode by default
+pub(crate) const MAX_DYNAMIC_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Maximum total size (in bytes) of the files cached in dynamic mode
+pub(crate) const MAX_DYNAMIC_CACHE_SIZE: usize = 256 * 1024 * 1024;
+
+/// File size (in bytes) above which files are streamed in dynamic mode by default
+pub(crate) const STREAM_THRESHOLD: u64 = 4 * 1024 * 1024;
+
 /// File mime types that can possibly be compressed
 pub(crate) const COMPRESS_TYPES: &[&str] = &[
     "text/html",
@@ -19,17 +30,30 @@ pub(crate) const COMPRESS_TYPES: &[&str] = &[
 ];
 
 /// Internal configuration shared across `MemoryServe` handlers.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(super) struct ServeOptions {
     pub(super) index_file: Option<&'static str>,
+    /// Whether the index file was configured, the default index file is
+    /// allowed to match no asset.
+    pub(super) explicit_index_file: bool,
     pub(super) index_on_subdirectories: bool,
     pub(super) fallback: Option<&'static str>,
     pub(super) fallback_status: StatusCode,
+    pub(super) spa_fallbacks: Vec<(&'static str, &'static str)>,
+    pub(super) error_pages: Vec<(StatusCode, &'static str)>,
+    pub(super) directory_listing: Option<DirectoryListing>,
     pub(super) html_cache_control: CacheControl,
     pub(super) cache_control: CacheControl,
     pub(super) enable_brotli: bool,
     pub(super) enable_gzip: bool,
     pub(super) enable_clean_url: bool,
+    pub(super) enable_csp_nonce: bool,
+    pub(super) canonical_urls: CanonicalUrls,
+    pub(super) headers: Vec<HeaderRule>,
+    pub(super) security_headers: SecurityHeaders,
+    pub(super) max_dynamic_size: Option<u64>,
+    pub(super) enable_dynamic_cache: bool,
+    pub(super) stream_threshold: u64,
 }
 
 impl Default for ServeOptions {
@@ -37,14 +61,25 @@ impl Default for ServeOptions {
     fn default() -> Self {
         Self {
             index_file: Some("/index.html"),
+            explicit_index_file: false,
             index_on_subdirectories: false,
             fallback: None,
             fallback_status: StatusCode::NOT_FOUND,
+            spa_fallbacks: Vec::new(),
+            error_pages: Vec::new(),
+            directory_listing: None,
             html_cache_control: CacheControl::Short,
             cache_control: CacheControl::Medium,
             enable_brotli: !cfg!(debug_assertions),
             enable_gzip: !cfg!(debug_assertions),
             enable_clean_url: false,
+            enable_csp_nonce: false,
+            canonical_urls: CanonicalUrls::default(),
+            headers: Vec::new(),
+            security_headers: SecurityHeaders::default().prepare(),
+            max_dynamic_size: Some(MAX_DYNAMIC_SIZE),
+            enable_dynamic_cache: true,
+            stream_threshold: STREAM_THRESHOLD,
         }
     }
 }
@@ -0,0 +1,340 @@
+use memmap2::MmapOptions;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Identifies an asset pack, both at the start of the pack and at the end of
+/// a binary with an appended pack.
+const PACK_MAGIC: &[u8; 8] = b"MSPACK01";
+
+/// Packs up to this size are copied to the heap instead of memory-mapped,
+/// which makes them independent of the file after startup.
+const HEAP_PACK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Environment variable to override the location of the asset pack.
+const PACK_ENV_NAME: &str = "MEMORY_SERVE_PACK";
+
+/// Contents of an asset pack, keyed by directory name and route.
+type PackIndex = HashMap<(&'static str, &'static str), &'static [u8]>;
+
+/// The asset pack is loaded (and verified) once.
+static PACK: OnceLock<Result<PackIndex, PackError>> = OnceLock::new();
+
+/// An asset pack written by the build step. The whole pack (index and data)
+/// is verified against the hash compiled into the binary at startup, which
+/// reads every page of the pack once. The mapped pages are backed by the
+/// file, so the operating system can drop them from memory afterwards.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct AssetPack {
+    pub file_name: &'static str,
+    pub hash: &'static str,
+}
+
+/// A problem while loading the asset pack.
+#[derive(Debug, Clone)]
+pub(crate) enum PackError {
+    NotFound(&'static str),
+    Unreadable(PathBuf, String),
+    HashMismatch(PathBuf),
+    Invalid(PathBuf),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(file_name) => write!(
+                f,
+                "memory-serve asset pack not found, append it to the binary, place {file_name} next to the binary or set {PACK_ENV_NAME}"
+            ),
+            Self::Unreadable(path, error) => {
+                write!(f, "could not read asset pack {}: {error}", path.display())
+            }
+            Self::HashMismatch(path) => write!(
+                f,
+                "asset pack {} does not match the binary, it was built for another version",
+                path.display()
+            ),
+            Self::Invalid(path) => write!(f, "asset pack {} is invalid", path.display()),
+        }
+    }
+}
+
+/// Serialize an asset pack: the magic bytes, the number of entries, the
+/// index with the directory name, route, offset and length of each entry and
+/// finally the data. All numbers are little endian.
+pub(crate) fn write_pack(entries: &[(&str, &str, &[u8])]) -> Vec<u8> {
+    let mut pack = PACK_MAGIC.to_vec();
+    let mut offset = 0u64;
+
+    pack.extend((entries.len() as u32).to_le_bytes());
+
+    for (name, route, bytes) in entries {
+        for value in [name, route] {
+            pack.extend((value.len() as u32).to_le_bytes());
+            pack.extend(value.as_bytes());
+        }
+
+        pack.extend(offset.to_le_bytes());
+        pack.extend((bytes.len() as u64).to_le_bytes());
+        offset += bytes.len() as u64;
+    }
+
+    for (_, _, bytes) in entries {
+        pack.extend(*bytes);
+    }
+
+    pack
+}
+
+/// Reads the fields of an asset pack.
+struct PackReader {
+    rest: &'static [u8],
+}
+
+impl PackReader {
+    fn take(&mut self, len: usize) -> Option<&'static [u8]> {
+        let (value, rest) = self.rest.split_at_checked(len)?;
+        self.rest = rest;
+
+        Some(value)
+    }
+
+    fn number<const N: usize>(&mut self) -> Option<usize> {
+        let mut bytes = [0; 8];
+        bytes[..N].copy_from_slice(self.take(N)?);
+
+        usize::try_from(u64::from_le_bytes(bytes)).ok()
+    }
+
+    fn string(&mut self) -> Option<&'static str> {
+        let len = self.number::<4>()?;
+
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+}
+
+/// Parse the index of an asset pack, returning `None` if it is malformed.
+fn parse_pack(pack: &'static [u8]) -> Option<PackIndex> {
+    let mut reader = PackReader {
+        rest: pack.strip_prefix(PACK_MAGIC)?,
+    };
+    let count = reader.number::<4>()?;
+    let mut entries = Vec::with_capacity(count.min(pack.len()));
+
+    for _ in 0..count {
+        let name = reader.string()?;
+        let route = reader.string()?;
+        let offset = reader.number::<8>()?;
+        let len = reader.number::<8>()?;
+
+        entries.push((name, route, offset, len));
+    }
+
+    let data = reader.rest;
+
+    entries
+        .into_iter()
+        .map(|(name, route, offset, len)| {
+            Some(((name, route), data.get(offset..offset.checked_add(len)?)?))
+        })
+        .collect()
+}
+
+/// Memory-map (a part of) a file, leaking the mapping to keep the assets
+/// available. A small pack is copied to the heap instead.
+fn map(path: &Path, range: Option<(u64, usize)>) -> Result<&'static [u8], PackError> {
+    let unreadable = |error: io::Error| PackError::Unreadable(path.to_owned(), error.to_string());
+    let mut file = File::open(path).map_err(unreadable)?;
+    let (offset, len) = match range {
+        Some((offset, len)) => (offset, len as u64),
+        None => (0, file.metadata().map_err(unreadable)?.len()),
+    };
+
+    if len <= HEAP_PACK_SIZE {
+        let mut bytes = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(offset)).map_err(unreadable)?;
+        file.take(len).read_to_end(&mut bytes).map_err(unreadable)?;
+
+        return Ok(bytes.leak());
+    }
+
+    let mut options = MmapOptions::new();
+
+    if let Some((offset, len)) = range {
+        options.offset(offset).len(len);
+    }
+
+    // SAFETY: the mapping is only sound while the file does not change.
+    // Writing to the mapped range would change the `'static` asset bytes and
+    // truncating the file makes reading them fail with SIGBUS. This is
+    // required of users in the documentation of `BuildOptions::asset_pack`
+    // and `append_asset_pack`: a pack (or a binary with an appended pack) must
+    // be replaced atomically, by renaming a new file over it, and never be
+    // modified in place while the server is running.
+    let mmap = unsafe { options.map(&file) }.map_err(unreadable)?;
+
+    Ok(Box::leak(Box::new(mmap)))
+}
+
+/// Find the position of a pack appended to the running binary, using the
+/// trailer written by [`append_asset_pack`].
+fn find_appended(binary: &Path) -> Option<(u64, usize)> {
+    let mut file = File::open(binary).ok()?;
+    let mut trailer = [0; 16];
+    let trailer_start = file.seek(SeekFrom::End(-16)).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+
+    if &trailer[8..] != PACK_MAGIC {
+        return None;
+    }
+
+    let len = u64::from_le_bytes(trailer[..8].try_into().ok()?);
+
+    Some((trailer_start.checked_sub(len)?, usize::try_from(len).ok()?))
+}
+
+/// Locate and map the asset pack: the path set in `MEMORY_SERVE_PACK`, a pack
+/// appended to the binary or a pack file next to the binary.
+fn find(pack: &AssetPack) -> Result<(PathBuf, &'static [u8]), PackError> {
+    if let Some(path) = std::env::var_os(PACK_ENV_NAME) {
+        let path = PathBuf::from(path);
+
+        return map(&path, None).map(|bytes| (path, bytes));
+    }
+
+    let binary = std::env::current_exe()
+        .map_err(|error| PackError::Unreadable(PathBuf::new(), error.to_string()))?;
+
+    if let Some(range) = find_appended(&binary) {
+        return map(&binary, Some(range)).map(|bytes| (binary, bytes));
+    }
+
+    let path = binary
+        .parent()
+        .map(|directory| directory.join(pack.file_name))
+        .filter(|path| path.is_file())
+        .ok_or(PackError::NotFound(pack.file_name))?;
+
+    map(&path, None).map(|bytes| (path, bytes))
+}
+
+/// Verify the mapped pack against the hash compiled into the binary, reading
+/// all of it, and parse its index.
+fn open(path: PathBuf, bytes: &'static [u8], pack: &AssetPack) -> Result<PackIndex, PackError> {
+    if sha256::digest(bytes) != pack.hash {
+        return Err(PackError::HashMismatch(path));
+    }
+
+    parse_pack(bytes).ok_or(PackError::Invalid(path))
+}
+
+/// Load the asset pack and verify it against the hash compiled into the
+/// binary, returning the contents of the assets.
+pub(crate) fn load(pack: &AssetPack) -> Result<&'static PackIndex, PackError> {
+    PACK.get_or_init(|| {
+        let (path, bytes) = find(pack)?;
+
+        open(path, bytes, pack)
+    })
+    .as_ref()
+    .map_err(Clone::clone)
+}
+
+#[cfg(test)]
+/// Load the asset pack at the path instead of searching for it.
+pub(crate) fn load_path(path: &Path, pack: &AssetPack) -> Result<&'static PackIndex, PackError> {
+    PACK.get_or_init(|| open(path.to_owned(), map(path, None)?, pack))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Append an asset pack, written by the build step when
+/// [`BuildOptions::asset_pack`](crate::BuildOptions::asset_pack) is enabled,
+/// to a (release) binary, e.g. from a packaging script. The pack is found at
+/// startup using a trailer with its length.
+///
+/// A pack larger than 8 MiB is memory-mapped from the binary: never modify
+/// or truncate a binary that is running, deploy a new binary by renaming it
+/// over the old one.
+pub fn append_asset_pack(binary: impl AsRef<Path>, pack: impl AsRef<Path>) -> io::Result<()> {
+    let pack = std::fs::read(pack)?;
+    let mut file = std::fs::OpenOptions::new().append(true).open(binary)?;
+
+    file.write_all(&pack)?;
+    file.write_all(&(pack.len() as u64).to_le_bytes())?;
+    file.write_all(PACK_MAGIC)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AssetPack, PackError, append_asset_pack, find_appended, map, open, parse_pack, write_pack,
+    };
+
+    #[test]
+    fn pack() {
+        let pack = write_pack(&[
+            ("default", "/index.html", b"<p>hi</p>"),
+            ("default", "/empty.txt", b""),
+            ("docs", "/index.html", b"docs"),
+        ]);
+        let index = parse_pack(pack.clone().leak()).unwrap();
+
+        assert_eq!(index[&("default", "/index.html")], b"<p>hi</p>");
+        assert_eq!(index[&("default", "/empty.txt")], b"");
+        assert_eq!(index[&("docs", "/index.html")], b"docs");
+        assert!(parse_pack(pack[..pack.len() - 1].to_vec().leak()).is_none());
+        assert!(parse_pack(b"MSPACK01").is_none());
+
+        let base = std::env::temp_dir().join(format!("memory-serve-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("binary"), b"\x7fELF...").unwrap();
+        std::fs::write(base.join("memory_serve.pack"), &pack).unwrap();
+
+        assert!(find_appended(&base.join("binary")).is_none());
+        append_asset_pack(base.join("binary"), base.join("memory_serve.pack")).unwrap();
+        let range = find_appended(&base.join("binary")).unwrap();
+        assert_eq!(map(&base.join("binary"), Some(range)).unwrap(), pack);
+
+        // a small pack is copied, it does not change with the file
+        let mapped = map(&base.join("memory_serve.pack"), None).unwrap();
+        std::fs::write(base.join("memory_serve.pack"), b"").unwrap();
+        assert_eq!(mapped, pack);
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn hash_mismatch() {
+        let base = std::env::temp_dir().join(format!("memory-serve-tamper-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        let path = base.join("memory_serve.pack");
+
+        let mut pack = write_pack(&[("default", "/index.html", b"<p>hi</p>")]);
+        let hash: &'static str = sha256::digest(&pack).leak();
+        let asset_pack = AssetPack {
+            file_name: "memory_serve.pack",
+            hash,
+        };
+
+        std::fs::write(&path, &pack).unwrap();
+        let index = open(path.clone(), map(&path, None).unwrap(), &asset_pack).unwrap();
+        assert_eq!(index[&("default", "/index.html")], b"<p>hi</p>");
+
+        // tamper with the contents of an asset
+        *pack.last_mut().unwrap() = b'!';
+        std::fs::write(&path, &pack).unwrap();
+        let result = open(path.clone(), map(&path, None).unwrap(), &asset_pack);
+        assert!(matches!(result, Err(PackError::HashMismatch(p)) if p == path));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
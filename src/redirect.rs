@@ -0,0 +1,346 @@
+use axum::{
+    Router,
+    extract::Request,
+    http::{HeaderValue, StatusCode, Uri, header::LOCATION},
+    response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+use tracing::{debug, warn};
+
+use crate::util::pattern::{fill_placeholders, match_placeholders};
+
+/// Default status of a rule in a `_redirects` file without status.
+const DEFAULT_STATUS: u16 = 301;
+
+/// Marks a request that was rewritten by a rule, rules are not applied
+/// to rewritten requests to prevent loops.
+#[derive(Debug, Clone, Copy)]
+struct Rewritten;
+
+/// A redirect or rewrite rule, as found in a Netlify style `_redirects` file.
+///
+/// The `from` pattern can contain `:name` placeholders, matching a single
+/// path segment, and end with a `*` splat, matching the remainder of the
+/// path. Placeholders and the splat (as `:splat`) can be used in `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redirect {
+    /// Path pattern to match, e.g. `/blog/:year/*`.
+    pub from: &'static str,
+    /// Query parameters the request must have, values starting with a colon
+    /// are placeholders that match any value.
+    pub query: &'static [(&'static str, &'static str)],
+    /// Target path or URL, e.g. `/news/:year/:splat`.
+    pub to: &'static str,
+    /// A 3xx status redirects the client, any other status serves the
+    /// target route (a rewrite) using that status.
+    pub status: u16,
+    /// Whether the rule also applies to paths that match an asset, by
+    /// default a rule only applies when no asset matches.
+    pub force: bool,
+}
+
+impl Redirect {
+    /// Create a rule from a path pattern to a target with the given status.
+    pub const fn new(from: &'static str, to: &'static str, status: u16) -> Self {
+        Self {
+            from,
+            query: &[],
+            to,
+            status,
+            force: false,
+        }
+    }
+
+    /// Only match requests with these query parameters.
+    pub const fn query(mut self, query: &'static [(&'static str, &'static str)]) -> Self {
+        self.query = query;
+
+        self
+    }
+
+    /// Whether to apply the rule even if an asset matches the path.
+    pub const fn force(mut self, force: bool) -> Self {
+        self.force = force;
+
+        self
+    }
+
+    /// Whether the rule redirects the client, instead of rewriting the route.
+    fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.status)
+    }
+
+    /// Match the request path and query, returning the target URL.
+    fn target(&self, path: &str, query: Option<&str>) -> Option<String> {
+        let mut captures = match_placeholders(self.from, path)?;
+
+        let params: Vec<(String, String)> = query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (key, value) = p.split_once('=').unwrap_or((p, ""));
+                let decode = |s: &str| urlencoding::decode(s).map(|s| s.into_owned());
+
+                (
+                    decode(key).unwrap_or_else(|_| key.to_string()),
+                    decode(value).unwrap_or_else(|_| value.to_string()),
+                )
+            })
+            .collect();
+
+        for (key, expected) in self.query {
+            let (_, value) = params.iter().find(|(k, _)| k == key)?;
+
+            match expected.strip_prefix(':') {
+                Some(name) => captures.push((name, urlencoding::encode(value).into_owned())),
+                None if value == expected => {}
+                None => return None,
+            }
+        }
+
+        let mut target = fill_placeholders(self.to, &captures);
+
+        // a substituted value must not turn a local target into a protocol
+        // relative URL, e.g. `/*  /:splat` for a request to `/\evil.example`
+        if self.to.starts_with('/') && !self.to.starts_with("//") {
+            target = format!("/{}", target.trim_start_matches(['/', '\\']));
+        }
+
+        // pass the query string on when the rule does not use it
+        if let Some(query) = query
+            && self.query.is_empty()
+            && !target.contains('?')
+        {
+            target = format!("{target}?{query}");
+        }
+
+        Some(target)
+    }
+
+    /// Parse the rules in a `_redirects` file. Each line contains a path,
+    /// optional query parameters (`key=value` or `key=:placeholder`), a target
+    /// and an optional status, followed by `!` to force the rule.
+    /// Empty lines and lines starting with `#` are ignored, invalid lines are
+    /// reported using the log function and skipped.
+    /// Caution! This leaks memory to create `'static` rules.
+    pub(crate) fn parse(contents: &str, log: impl Fn(&str)) -> Vec<Self> {
+        contents
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let line = line.split(" #").next().unwrap_or_default().trim();
+
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+
+                let result = Self::parse_line(line);
+
+                if let Err(message) = &result {
+                    log(&format!(
+                        "skipping redirect on line {}: {message}",
+                        index + 1
+                    ));
+                }
+
+                result.ok()
+            })
+            .collect()
+    }
+
+    /// Parse a single (non-empty) rule.
+    fn parse_line(line: &str) -> Result<Self, String> {
+        let mut tokens = line.split_whitespace().peekable();
+        let leak = |s: &str| -> &'static str { s.to_string().leak() };
+
+        let from = tokens.next().ok_or("missing path")?;
+
+        if !from.starts_with('/') {
+            return Err(format!("path {from:?} should start with a slash"));
+        }
+
+        let mut query = Vec::new();
+
+        while let Some(token) = tokens.next_if(|t| !t.starts_with('/') && !t.contains("://")) {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| format!("invalid query parameter {token:?}"))?;
+            query.push((leak(key), leak(value)));
+        }
+
+        let to = tokens.next().ok_or("missing target")?;
+
+        let (status, force) = match tokens.next() {
+            Some(status) => {
+                let force = status.ends_with('!');
+                let status = status
+                    .trim_end_matches('!')
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|s| (200..600).contains(s))
+                    .ok_or_else(|| format!("invalid status {status:?}"))?;
+
+                (status, force)
+            }
+            None => (DEFAULT_STATUS, false),
+        };
+
+        if let Some(token) = tokens.next() {
+            return Err(format!("unexpected {token:?}"));
+        }
+
+        if !(300..400).contains(&status) && !to.starts_with('/') {
+            return Err(format!(
+                "can not rewrite to {to:?}, proxying is not supported"
+            ));
+        }
+
+        Ok(Self {
+            from: leak(from),
+            query: query.leak(),
+            to: leak(to),
+            status,
+            force,
+        })
+    }
+
+    /// Generate the code to construct the rule.
+    pub(crate) fn to_code(self) -> String {
+        format!(
+            "memory_serve::Redirect {{ from: {:?}, query: &{:?}, to: {:?}, status: {}, force: {} }}",
+            self.from, self.query, self.to, self.status, self.force
+        )
+    }
+}
+
+/// Apply the first matching rule to the request, either redirecting or
+/// serving the target route from the site. Returns the request when no rule
+/// matches or the request was already rewritten.
+pub(crate) async fn apply_redirects(
+    rules: impl Iterator<Item = &Redirect>,
+    site: &Router,
+    mut request: Request,
+) -> Result<Response, Request> {
+    if request.extensions().get::<Rewritten>().is_some() {
+        return Err(request);
+    }
+
+    let path = request.uri().path();
+    let query = request.uri().query();
+
+    let Some((rule, target)) = rules
+        .filter_map(|rule| rule.target(path, query).map(|target| (rule, target)))
+        .next()
+    else {
+        return Err(request);
+    };
+
+    let status = StatusCode::from_u16(rule.status).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+
+    if rule.is_redirect() {
+        debug!("redirecting {path} to {target} ({status})");
+
+        return Ok(match HeaderValue::try_from(target) {
+            Ok(location) => (status, [(LOCATION, location)]).into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        });
+    }
+
+    debug!("rewriting {path} to {target} ({status})");
+
+    let Ok(uri) = target.parse::<Uri>() else {
+        warn!("invalid rewrite target {target}");
+
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    };
+
+    *request.uri_mut() = uri;
+    request.extensions_mut().insert(Rewritten);
+
+    let mut response = site.clone().oneshot(request).await.into_response();
+
+    if status != StatusCode::OK && response.status().is_success() {
+        *response.status_mut() = status;
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Redirect;
+
+    #[test]
+    fn parse() {
+        let rules = Redirect::parse(
+            "
+            # comment
+            /home              /                    301
+            /blog/*            /news/:splat         302!
+            /store id=:id      /products/:id        301 # trailing comment
+            /app/*             /index.html          200
+            /external          https://example.com
+            /proxy             https://example.com  200
+            invalid            /
+            /a                 /b                   999
+            ",
+            |_| {},
+        );
+
+        assert_eq!(
+            rules,
+            [
+                Redirect::new("/home", "/", 301),
+                Redirect::new("/blog/*", "/news/:splat", 302).force(true),
+                Redirect::new("/store", "/products/:id", 301).query(&[("id", ":id")]),
+                Redirect::new("/app/*", "/index.html", 200),
+                Redirect::new("/external", "https://example.com", 301),
+            ]
+        );
+    }
+
+    #[test]
+    fn target() {
+        let rule = Redirect::new("/blog/:year/*", "/news/:year/:splat", 301);
+        assert_eq!(
+            rule.target("/blog/2024/a/b", Some("x=1")).unwrap(),
+            "/news/2024/a/b?x=1"
+        );
+        assert!(rule.target("/news/2024/a/b", None).is_none());
+
+        let rule = Redirect::new("/store", "/products/:id", 301).query(&[("id", ":id")]);
+        assert_eq!(
+            rule.target("/store", Some("id=a%20b&x=1")).unwrap(),
+            "/products/a%20b"
+        );
+        assert!(rule.target("/store", Some("x=1")).is_none());
+
+        let rule = Redirect::new("/store", "/sale", 301).query(&[("type", "sale")]);
+        assert!(rule.target("/store", Some("type=sale")).is_some());
+        assert!(rule.target("/store", Some("type=new")).is_none());
+
+        let rule = Redirect::new("/*", "/:splat", 301);
+        assert_eq!(
+            rule.target("/\\evil.example", None).unwrap(),
+            "/evil.example"
+        );
+        assert_eq!(rule.target("/a//b", None).unwrap(), "/a//b");
+
+        let rule = Redirect::new("/go/*", "/:splat", 301);
+        assert_eq!(
+            rule.target("/go//evil.example", None).unwrap(),
+            "/evil.example"
+        );
+        assert_eq!(
+            rule.target("/go/\\/evil.example", None).unwrap(),
+            "/evil.example"
+        );
+
+        let rule = Redirect::new("/cdn/*", "//cdn.example/:splat", 301);
+        assert_eq!(
+            rule.target("/cdn/a.js", None).unwrap(),
+            "//cdn.example/a.js"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+
+use crate::{asset::ServedAsset, options::ServeOptions};
+
+/// A response serving a single asset from a custom handler, obtained using
+/// [`MemoryServe::respond`](crate::MemoryServe::respond). The content
+/// encoding, `ETag` validation and cache control are the same as for the
+/// routes of the router.
+#[derive(Debug)]
+pub struct AssetReply<'a> {
+    served: ServedAsset,
+    path: String,
+    options: &'a ServeOptions,
+    request_headers: HeaderMap,
+    status: StatusCode,
+    headers: HeaderMap,
+}
+
+impl<'a> AssetReply<'a> {
+    pub(crate) fn new(
+        served: ServedAsset,
+        path: &str,
+        options: &'a ServeOptions,
+        request_headers: &HeaderMap,
+    ) -> Self {
+        Self {
+            served,
+            path: path.to_string(),
+            options,
+            request_headers: request_headers.clone(),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Respond with a different status, `200 OK` by default.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+
+        self
+    }
+
+    /// Add a header to the response, replacing a header with the same name
+    /// set by memory-serve.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+
+        self
+    }
+}
+
+impl AssetReply<'_> {
+    /// Create the response, a dynamically loaded asset is read and compressed
+    /// without blocking the runtime.
+    pub async fn into_response(self) -> Response {
+        let mut response = self
+            .served
+            .handler(&self.path, &self.request_headers, self.status, self.options)
+            .await;
+
+        response.headers_mut().extend(self.headers);
+
+        response
+    }
+}
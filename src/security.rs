@@ -0,0 +1,429 @@
+use axum::http::{
+    HeaderMap, HeaderName, HeaderValue,
+    header::{
+        CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+    },
+};
+use std::fmt;
+
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+const CROSS_ORIGIN_OPENER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-opener-policy");
+const CROSS_ORIGIN_EMBEDDER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-embedder-policy");
+const CROSS_ORIGIN_RESOURCE_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-resource-policy");
+
+/// Stands in for the nonce in the prepared nonce policy, it can not occur in
+/// a valid header value.
+const NONCE_PLACEHOLDER: &str = "\0";
+
+/// Permissions policy that disables powerful browser features.
+const RESTRICTIVE_PERMISSIONS: &str = "accelerometer=(), camera=(), geolocation=(), gyroscope=(), magnetometer=(), microphone=(), payment=(), usb=()";
+
+/// Builder for the value of the `Content-Security-Policy` header.
+/// Directives are rendered in the order they were first added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(&'static str, Vec<String>)>,
+}
+
+impl ContentSecurityPolicy {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Policy that only allows resources from the same origin, disallows
+    /// inline scripts and styles, plugins, framing and changing the base URI.
+    pub fn strict() -> Self {
+        Self::new()
+            .default_src(["'none'"])
+            .script_src(["'self'"])
+            .style_src(["'self'"])
+            .img_src(["'self'"])
+            .font_src(["'self'"])
+            .connect_src(["'self'"])
+            .directive("manifest-src", ["'self'"])
+            .object_src(["'none'"])
+            .base_uri(["'none'"])
+            .form_action(["'self'"])
+            .frame_ancestors(["'none'"])
+    }
+
+    /// Policy for single page applications: same origin resources, inline
+    /// styles and `data:` / `blob:` images are allowed, plugins are not.
+    pub fn spa() -> Self {
+        Self::new()
+            .default_src(["'self'"])
+            .style_src(["'self'", "'unsafe-inline'"])
+            .img_src(["'self'", "data:", "blob:"])
+            .object_src(["'none'"])
+            .base_uri(["'self'"])
+            .frame_ancestors(["'self'"])
+    }
+
+    /// Add sources to a directive, e.g. `directive("worker-src", ["'self'"])`.
+    /// Directives without sources (like `upgrade-insecure-requests`) can be
+    /// added by passing an empty list.
+    pub fn directive<I, V>(mut self, name: &'static str, sources: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        let sources = sources.into_iter().map(Into::into);
+
+        match self.directives.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => existing.extend(sources),
+            None => self.directives.push((name, sources.collect())),
+        }
+
+        self
+    }
+
+    /// Sources for the `default-src` directive.
+    pub fn default_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("default-src", sources)
+    }
+
+    /// Sources for the `script-src` directive.
+    pub fn script_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("script-src", sources)
+    }
+
+    /// Sources for the `style-src` directive.
+    pub fn style_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("style-src", sources)
+    }
+
+    /// Sources for the `img-src` directive.
+    pub fn img_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("img-src", sources)
+    }
+
+    /// Sources for the `font-src` directive.
+    pub fn font_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("font-src", sources)
+    }
+
+    /// Sources for the `connect-src` directive.
+    pub fn connect_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("connect-src", sources)
+    }
+
+    /// Sources for the `object-src` directive.
+    pub fn object_src<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("object-src", sources)
+    }
+
+    /// Sources for the `base-uri` directive.
+    pub fn base_uri<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("base-uri", sources)
+    }
+
+    /// Sources for the `form-action` directive.
+    pub fn form_action<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("form-action", sources)
+    }
+
+    /// Sources for the `frame-ancestors` directive.
+    pub fn frame_ancestors<I: IntoIterator<Item = V>, V: Into<String>>(self, sources: I) -> Self {
+        self.directive("frame-ancestors", sources)
+    }
+
+    /// Allow scripts and styles carrying the nonce, in the directives that
+    /// restrict them. When the policy has no `script-src` or `style-src`
+    /// directive, the `default-src` sources are copied so that the nonce does
+    /// not restrict the policy further. Directives that are unrestricted (no
+    /// directive and no `default-src`) or allow `'unsafe-inline'` are left
+    /// as is, since browsers ignore `'unsafe-inline'` when a nonce is present.
+    pub(crate) fn with_nonce(&self, nonce: &str) -> Self {
+        let defaults = self
+            .directives
+            .iter()
+            .find(|(name, _)| *name == "default-src")
+            .map(|(_, sources)| sources.clone());
+        let source = format!("'nonce-{nonce}'");
+        let mut policy = self.clone();
+
+        for name in ["script-src", "style-src"] {
+            let existing = policy
+                .directives
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, sources)| sources);
+            let Some(sources) = existing.or(defaults.as_ref()) else {
+                continue;
+            };
+
+            if sources.iter().any(|source| source == "'unsafe-inline'") {
+                continue;
+            }
+
+            if existing.is_none() {
+                policy = policy.directive(name, defaults.clone().unwrap_or_default());
+            }
+
+            policy = policy.directive(name, [source.clone()]);
+        }
+
+        policy
+    }
+}
+
+impl fmt::Display for ContentSecurityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, sources)) in self.directives.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+
+            f.write_str(name)?;
+
+            for source in sources {
+                write!(f, " {source}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Security related headers added to every response.
+/// Document level headers (CSP, referrer and permissions policy, COOP and
+/// COEP) are only sent with HTML files, the others with all files.
+/// `SecurityHeaders::default()` sends no headers at all.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityHeaders {
+    strict_transport_security: Option<&'static str>,
+    content_type_options: bool,
+    referrer_policy: Option<&'static str>,
+    permissions_policy: Option<&'static str>,
+    cross_origin_opener_policy: Option<&'static str>,
+    cross_origin_embedder_policy: Option<&'static str>,
+    cross_origin_resource_policy: Option<&'static str>,
+    content_security_policy: Option<ContentSecurityPolicy>,
+    /// The rendered `content_security_policy`, see [`SecurityHeaders::prepare`].
+    policy_value: Option<HeaderValue>,
+    /// The rendered nonce policy with a placeholder for the nonce.
+    nonce_template: Option<String>,
+}
+
+impl SecurityHeaders {
+    /// Strictest preset, suitable for sites that only load resources
+    /// from their own origin and do not use inline scripts or styles.
+    pub fn strict() -> Self {
+        Self {
+            strict_transport_security: Some("max-age=63072000; includeSubDomains"),
+            content_type_options: true,
+            referrer_policy: Some("no-referrer"),
+            permissions_policy: Some(RESTRICTIVE_PERMISSIONS),
+            cross_origin_opener_policy: Some("same-origin"),
+            cross_origin_embedder_policy: Some("require-corp"),
+            cross_origin_resource_policy: Some("same-origin"),
+            content_security_policy: Some(ContentSecurityPolicy::strict()),
+            ..Default::default()
+        }
+    }
+
+    /// Preset for single page applications, which allows inline styles,
+    /// `data:` images and embedding cross-origin resources.
+    pub fn spa() -> Self {
+        Self {
+            strict_transport_security: Some("max-age=31536000"),
+            content_type_options: true,
+            referrer_policy: Some("strict-origin-when-cross-origin"),
+            permissions_policy: Some(RESTRICTIVE_PERMISSIONS),
+            cross_origin_opener_policy: Some("same-origin"),
+            cross_origin_embedder_policy: None,
+            cross_origin_resource_policy: Some("same-origin"),
+            content_security_policy: Some(ContentSecurityPolicy::spa()),
+            ..Default::default()
+        }
+    }
+
+    /// Value of the `Strict-Transport-Security` header, e.g. `"max-age=31536000"`.
+    pub fn strict_transport_security(mut self, value: Option<&'static str>) -> Self {
+        self.strict_transport_security = value;
+
+        self
+    }
+
+    /// Whether to send `X-Content-Type-Options: nosniff`.
+    pub fn content_type_options(mut self, nosniff: bool) -> Self {
+        self.content_type_options = nosniff;
+
+        self
+    }
+
+    /// Value of the `Referrer-Policy` header, e.g. `"no-referrer"`.
+    pub fn referrer_policy(mut self, value: Option<&'static str>) -> Self {
+        self.referrer_policy = value;
+
+        self
+    }
+
+    /// Value of the `Permissions-Policy` header, e.g. `"camera=()"`.
+    pub fn permissions_policy(mut self, value: Option<&'static str>) -> Self {
+        self.permissions_policy = value;
+
+        self
+    }
+
+    /// Value of the `Cross-Origin-Opener-Policy` header, e.g. `"same-origin"`.
+    pub fn cross_origin_opener_policy(mut self, value: Option<&'static str>) -> Self {
+        self.cross_origin_opener_policy = value;
+
+        self
+    }
+
+    /// Value of the `Cross-Origin-Embedder-Policy` header, e.g. `"require-corp"`.
+    pub fn cross_origin_embedder_policy(mut self, value: Option<&'static str>) -> Self {
+        self.cross_origin_embedder_policy = value;
+
+        self
+    }
+
+    /// Value of the `Cross-Origin-Resource-Policy` header, e.g. `"same-site"`.
+    pub fn cross_origin_resource_policy(mut self, value: Option<&'static str>) -> Self {
+        self.cross_origin_resource_policy = value;
+
+        self
+    }
+
+    /// The `Content-Security-Policy` to send with HTML files.
+    pub fn content_security_policy(mut self, policy: Option<ContentSecurityPolicy>) -> Self {
+        self.content_security_policy = policy;
+
+        self
+    }
+
+    /// The configured policy, used as base for the nonce policy. Without a
+    /// configured policy, only same origin and nonce carrying scripts and
+    /// styles are allowed.
+    fn nonce_base_policy(&self) -> ContentSecurityPolicy {
+        self.content_security_policy.clone().unwrap_or_else(|| {
+            ContentSecurityPolicy::new()
+                .script_src(["'self'"])
+                .style_src(["'self'"])
+                .object_src(["'none'"])
+                .base_uri(["'self'"])
+        })
+    }
+
+    /// Render the policies once, so they are not rendered for every response.
+    /// Panics when the policy is not a valid header value.
+    pub(crate) fn prepare(mut self) -> Self {
+        self.policy_value = self.content_security_policy.as_ref().map(|policy| {
+            HeaderValue::from_str(&policy.to_string())
+                .unwrap_or_else(|_| panic!("invalid Content-Security-Policy: {policy}"))
+        });
+
+        let template = self
+            .nonce_base_policy()
+            .with_nonce(NONCE_PLACEHOLDER)
+            .to_string();
+        let example = template.replace(NONCE_PLACEHOLDER, "nonce");
+
+        if HeaderValue::from_str(&example).is_err() {
+            panic!("invalid Content-Security-Policy: {example}");
+        }
+
+        self.nonce_template = Some(template);
+
+        self
+    }
+
+    /// The `Content-Security-Policy` for an HTML document containing the nonce.
+    pub(crate) fn nonce_policy(&self, nonce: &str) -> HeaderValue {
+        let policy = match &self.nonce_template {
+            Some(template) => template.replace(NONCE_PLACEHOLDER, nonce),
+            None => self.nonce_base_policy().with_nonce(nonce).to_string(),
+        };
+
+        // the template is validated in `prepare` and the nonce is hex encoded
+        HeaderValue::from_str(&policy).expect("invalid Content-Security-Policy")
+    }
+
+    /// Add the configured headers for an HTML or non-HTML asset.
+    pub(crate) fn apply(&self, is_html: bool, headers: &mut HeaderMap) {
+        let mut insert = |name: HeaderName, value: Option<&'static str>| {
+            if let Some(value) = value {
+                headers.insert(name, HeaderValue::from_static(value));
+            }
+        };
+
+        insert(STRICT_TRANSPORT_SECURITY, self.strict_transport_security);
+        insert(
+            X_CONTENT_TYPE_OPTIONS,
+            self.content_type_options.then_some("nosniff"),
+        );
+        insert(
+            CROSS_ORIGIN_RESOURCE_POLICY,
+            self.cross_origin_resource_policy,
+        );
+
+        if !is_html {
+            return;
+        }
+
+        insert(REFERRER_POLICY, self.referrer_policy);
+        insert(PERMISSIONS_POLICY, self.permissions_policy);
+        insert(CROSS_ORIGIN_OPENER_POLICY, self.cross_origin_opener_policy);
+        insert(
+            CROSS_ORIGIN_EMBEDDER_POLICY,
+            self.cross_origin_embedder_policy,
+        );
+
+        if let Some(policy) = &self.policy_value {
+            headers.insert(CONTENT_SECURITY_POLICY, policy.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentSecurityPolicy, SecurityHeaders};
+
+    #[test]
+    fn content_security_policy() {
+        assert_eq!(ContentSecurityPolicy::new().to_string(), "");
+
+        let policy = ContentSecurityPolicy::new()
+            .default_src(["'self'"])
+            .img_src(["'self'", "https://example.com"])
+            .directive("upgrade-insecure-requests", Vec::<String>::new())
+            .default_src(["data:"]);
+
+        assert_eq!(
+            policy.to_string(),
+            "default-src 'self' data:; img-src 'self' https://example.com; upgrade-insecure-requests"
+        );
+        assert_eq!(
+            policy
+                .clone()
+                .style_src(["'self'"])
+                .with_nonce("abc")
+                .to_string(),
+            "default-src 'self' data:; img-src 'self' https://example.com; upgrade-insecure-requests; style-src 'self' 'nonce-abc'; script-src 'self' data: 'nonce-abc'"
+        );
+
+        // inline styles stay allowed in the spa preset
+        assert_eq!(
+            ContentSecurityPolicy::spa().with_nonce("abc").to_string(),
+            "default-src 'self'; style-src 'self' 'unsafe-inline'; img-src 'self' data: blob:; object-src 'none'; base-uri 'self'; frame-ancestors 'self'; script-src 'self' 'nonce-abc'"
+        );
+
+        // unrestricted directives are not restricted by the nonce
+        let policy = ContentSecurityPolicy::new().img_src(["'self'"]);
+        assert_eq!(policy.with_nonce("abc"), policy);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid Content-Security-Policy")]
+    fn invalid_policy() {
+        SecurityHeaders::default()
+            .content_security_policy(Some(ContentSecurityPolicy::new().default_src(["'self'\n"])))
+            .prepare();
+    }
+}
@@ -0,0 +1,13 @@
+/// Run blocking or CPU heavy work, like compressing or hashing a file, on the
+/// blocking thread pool instead of a runtime worker thread. A panic in the
+/// work is resumed in the calling task.
+pub(crate) async fn spawn_blocking<T, F>(work: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(error) => std::panic::resume_unwind(error.into_panic()),
+    }
+}
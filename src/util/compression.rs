@@ -10,7 +10,12 @@ pub(crate) fn decompress_brotli(input: &[u8]) -> Option<Vec<u8>> {
 
 /// Compress a byte slice using brotli.
 pub(crate) fn compress_brotli(input: &[u8]) -> Option<Vec<u8>> {
-    let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    compress_brotli_with_quality(input, 11)
+}
+
+/// Compress a byte slice using brotli with the given quality (0 - 11).
+pub(crate) fn compress_brotli_with_quality(input: &[u8], quality: u32) -> Option<Vec<u8>> {
+    let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, quality, 22);
     writer.write_all(input).ok()?;
 
     Some(writer.into_inner())
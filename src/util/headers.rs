@@ -1,7 +1,8 @@
 use axum::http::{
     HeaderMap, HeaderName, HeaderValue,
-    header::{ACCEPT_ENCODING, CONTENT_LENGTH},
+    header::{ACCEPT_ENCODING, CONTENT_LENGTH, IF_MODIFIED_SINCE, IF_NONE_MATCH},
 };
+use std::time::SystemTime;
 
 /// Check if the client supports the given encoding.
 pub(crate) fn supports_encoding(headers: &HeaderMap, encoding: &str) -> bool {
@@ -34,10 +35,41 @@ pub(crate) fn content_length(len: usize) -> (HeaderName, HeaderValue) {
     (CONTENT_LENGTH, HeaderValue::from(len))
 }
 
+/// Check if the client has a fresh copy, using `If-None-Match` with the
+/// ETag (if known, otherwise empty) or, without that header,
+/// `If-Modified-Since` with the modification time.
+pub(crate) fn is_not_modified(
+    headers: &HeaderMap,
+    etag: &str,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        return !etag.is_empty() && if_none_match == etag;
+    }
+
+    let if_modified_since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+
+    match (if_modified_since, last_modified) {
+        // HTTP dates have a resolution of one second
+        (Some(since), Some(modified)) => modified
+            .duration_since(since)
+            .ok()
+            .is_none_or(|elapsed| elapsed.as_secs() == 0),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::supports_encoding;
-    use axum::http::{HeaderMap, HeaderValue, header::ACCEPT_ENCODING};
+    use super::{is_not_modified, supports_encoding};
+    use axum::http::{
+        HeaderMap, HeaderValue,
+        header::{ACCEPT_ENCODING, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    };
+    use std::time::{Duration, SystemTime};
 
     fn check(header: &str, encoding: &str) -> bool {
         let mut headers = HeaderMap::new();
@@ -61,4 +93,31 @@ mod tests {
         assert!(!check("gzip, compress, br", "zstd"));
         assert!(check("br;q=1.0, gzip;q=0.8, *;q=0.1", "zstd"));
     }
+
+    #[test]
+    fn not_modified() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let header = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+
+            headers
+        };
+
+        let headers = header(IF_NONE_MATCH, "abc");
+        assert!(is_not_modified(&headers, "abc", Some(modified)));
+        assert!(!is_not_modified(&headers, "def", Some(modified)));
+        assert!(!is_not_modified(&headers, "", Some(modified)));
+
+        let date = httpdate::fmt_http_date(modified);
+        let headers = header(IF_MODIFIED_SINCE, &date);
+        assert!(is_not_modified(&headers, "", Some(modified)));
+        assert!(!is_not_modified(
+            &headers,
+            "",
+            Some(modified + Duration::from_secs(2))
+        ));
+        assert!(!is_not_modified(&headers, "", None));
+        assert!(!is_not_modified(&HeaderMap::new(), "abc", Some(modified)));
+    }
 }
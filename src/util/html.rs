@@ -0,0 +1,324 @@
+/// Tags that receive a `nonce` attribute.
+const NONCE_TAGS: [&[u8]; 2] = [b"<script", b"<style"];
+
+/// Tags with a raw text body, which can contain text that looks like tags,
+/// with their closing tag.
+const RAW_TEXT_TAGS: [(&[u8], &[u8]); 2] = [(b"<script", b"</script"), (b"<style", b"</style")];
+
+/// Tags that can carry an `integrity` attribute, with the attribute
+/// containing the URL of the subresource.
+const INTEGRITY_TAGS: [(&[u8], &str); 2] = [(b"<script", "src"), (b"<link", "href")];
+
+/// Values of the `rel` attribute of `<link>` tags that support integrity.
+const INTEGRITY_LINK_RELS: [&str; 3] = ["stylesheet", "preload", "modulepreload"];
+
+/// Whether the input starts with the opening tag name, case insensitive.
+fn starts_with_tag(rest: &[u8], name: &[u8]) -> bool {
+    rest.len() > name.len()
+        && rest[..name.len()].eq_ignore_ascii_case(name)
+        && matches!(rest[name.len()], b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Find the opening tags with one of the given names, skipping HTML comments
+/// and the bodies of `<script>` and `<style>` tags.
+/// Returns the index of the matching name, the start of the tag and the
+/// offset directly after the tag name.
+fn find_tags(html: &[u8], names: &[&[u8]]) -> Vec<(usize, usize, usize)> {
+    let mut tags = Vec::new();
+    let mut index = 0;
+
+    while index < html.len() {
+        let rest = &html[index..];
+
+        if rest.starts_with(b"<!--") {
+            index += find(rest, b"-->").map(|end| end + 3).unwrap_or(rest.len());
+            continue;
+        }
+
+        let name = names.iter().position(|name| starts_with_tag(rest, name));
+
+        if let Some(name) = name {
+            tags.push((name, index, index + names[name].len()));
+        }
+
+        match RAW_TEXT_TAGS
+            .iter()
+            .find(|(name, _)| starts_with_tag(rest, name))
+        {
+            // continue at the closing tag, after the end of the opening tag
+            Some((name, closing)) => {
+                let body =
+                    find(&rest[name.len()..], b">").map_or(rest.len(), |end| name.len() + end + 1);
+                index +=
+                    body + find_ignore_case(&rest[body..], closing).unwrap_or(rest.len() - body);
+            }
+            None => index += name.map_or(1, |name| names[name].len()),
+        }
+    }
+
+    tags
+}
+
+/// Find the byte offsets directly after the tag name of every `<script>` and
+/// `<style>` opening tag, skipping HTML comments.
+pub(crate) fn nonce_offsets(html: &[u8]) -> Vec<usize> {
+    find_tags(html, &NONCE_TAGS)
+        .into_iter()
+        .map(|(_, _, name_end)| name_end)
+        .collect()
+}
+
+/// Parse the attributes of a tag, starting after the tag name and ending at
+/// the closing `>`. Attribute names are lowercased.
+fn parse_attributes(tag: &[u8]) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut index = 0;
+
+    loop {
+        while index < tag.len() && (tag[index].is_ascii_whitespace() || tag[index] == b'/') {
+            index += 1;
+        }
+
+        if index >= tag.len() || tag[index] == b'>' {
+            return attributes;
+        }
+
+        let name_start = index;
+        while index < tag.len() && !matches!(tag[index], b'=' | b'>' | b'/') {
+            if tag[index].is_ascii_whitespace() {
+                break;
+            }
+            index += 1;
+        }
+        let name = String::from_utf8_lossy(&tag[name_start..index]).to_ascii_lowercase();
+
+        while index < tag.len() && tag[index].is_ascii_whitespace() {
+            index += 1;
+        }
+
+        let mut value = String::new();
+
+        if index < tag.len() && tag[index] == b'=' {
+            index += 1;
+            while index < tag.len() && tag[index].is_ascii_whitespace() {
+                index += 1;
+            }
+
+            let value_start;
+            if index < tag.len() && matches!(tag[index], b'"' | b'\'') {
+                let quote = tag[index];
+                value_start = index + 1;
+                index = value_start;
+                while index < tag.len() && tag[index] != quote {
+                    index += 1;
+                }
+                value = String::from_utf8_lossy(&tag[value_start..index]).to_string();
+                index += 1;
+            } else {
+                value_start = index;
+                while index < tag.len() && !tag[index].is_ascii_whitespace() && tag[index] != b'>' {
+                    index += 1;
+                }
+                value = String::from_utf8_lossy(&tag[value_start..index]).to_string();
+            }
+        }
+
+        if name.is_empty() {
+            index += 1;
+        } else {
+            attributes.push((name, value));
+        }
+    }
+}
+
+/// Resolve a URL found in an HTML document to an asset route. Returns `None`
+/// for URLs pointing to another origin.
+pub(crate) fn resolve_route(document_route: &str, url: &str) -> Option<String> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+
+    if url.is_empty() || url.starts_with("//") || url.contains(':') {
+        return None;
+    }
+
+    let base = if url.starts_with('/') {
+        ""
+    } else {
+        &document_route[..document_route.rfind('/').unwrap_or_default()]
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in base.split('/').chain(url.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    Some(format!("/{}", segments.join("/")))
+}
+
+/// Insert `integrity` attributes into `<script src>` and `<link href>` tags
+/// referencing a route for which `lookup` returns an integrity string.
+/// Tags that already have an integrity attribute are left untouched.
+/// Returns `None` when no attribute was inserted.
+pub(crate) fn insert_integrity<'a>(
+    html: &[u8],
+    document_route: &str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+) -> Option<Vec<u8>> {
+    let names = INTEGRITY_TAGS.map(|(name, _)| name);
+    let mut result = Vec::with_capacity(html.len());
+    let mut start = 0;
+
+    for (name, _, name_end) in find_tags(html, &names) {
+        let attributes = parse_attributes(&html[name_end..]);
+        let attribute = |key: &str| {
+            attributes
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        if attribute("integrity").is_some() {
+            continue;
+        }
+
+        if name == 1
+            && !attribute("rel").is_some_and(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|rel| INTEGRITY_LINK_RELS.contains(&rel.to_ascii_lowercase().as_str()))
+            })
+        {
+            continue;
+        }
+
+        let Some(integrity) = attribute(INTEGRITY_TAGS[name].1)
+            .and_then(|url| resolve_route(document_route, url))
+            .and_then(|route| lookup(&route))
+        else {
+            continue;
+        };
+
+        result.extend_from_slice(&html[start..name_end]);
+        result.extend_from_slice(format!(" integrity=\"{integrity}\"").as_bytes());
+        start = name_end;
+    }
+
+    if start == 0 {
+        return None;
+    }
+
+    result.extend_from_slice(&html[start..]);
+
+    Some(result)
+}
+
+/// Insert a `nonce` attribute at each of the offsets.
+pub(crate) fn inject_nonce(html: &[u8], offsets: &[usize], nonce: &str) -> Vec<u8> {
+    let attribute = format!(" nonce=\"{nonce}\"");
+    let mut result = Vec::with_capacity(html.len() + offsets.len() * attribute.len());
+    let mut start = 0;
+
+    for offset in offsets.iter().copied().filter(|o| *o <= html.len()) {
+        result.extend_from_slice(&html[start..offset]);
+        result.extend_from_slice(attribute.as_bytes());
+        start = offset;
+    }
+
+    result.extend_from_slice(&html[start..]);
+
+    result
+}
+
+/// Generate a random, hex encoded, nonce of 128 bits.
+pub(crate) fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("could not generate a random nonce");
+
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Find the position of the first occurrence of a needle.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Find the position of the first occurrence of a needle, ignoring ASCII case.
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject_nonce, insert_integrity, nonce_offsets, resolve_route};
+
+    #[test]
+    fn nonces() {
+        let html = b"<html><SCRIPT src=\"/a.js\"></SCRIPT><!-- <script> --><style>a{}</style><scripts><script>1</script>";
+        let offsets = nonce_offsets(html);
+
+        assert_eq!(offsets, [13, 58, 86]);
+        assert_eq!(
+            String::from_utf8(inject_nonce(html, &offsets, "abc")).unwrap(),
+            "<html><SCRIPT nonce=\"abc\" src=\"/a.js\"></SCRIPT><!-- <script> --><style nonce=\"abc\">a{}</style><scripts><script nonce=\"abc\">1</script>"
+        );
+    }
+
+    #[test]
+    fn nonces_in_raw_text() {
+        let html = b"<script>let a = \"<script>\"; // <style>\n</SCRIPT><style>a::after { content: '<script>' }</style><script src=\"/a.js\"></script>";
+        let offsets = nonce_offsets(html);
+
+        assert_eq!(offsets, [7, 54, 102]);
+        assert_eq!(
+            String::from_utf8(inject_nonce(html, &offsets, "abc")).unwrap(),
+            "<script nonce=\"abc\">let a = \"<script>\"; // <style>\n</SCRIPT><style nonce=\"abc\">a::after { content: '<script>' }</style><script nonce=\"abc\" src=\"/a.js\"></script>"
+        );
+        assert!(
+            insert_integrity(
+                b"<script>document.write('<script src=\"/a.js\"></script>')</script>",
+                "/index.html",
+                |_| Some("sha384-a")
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn routes() {
+        assert_eq!(resolve_route("/index.html", "/a.js").unwrap(), "/a.js");
+        assert_eq!(resolve_route("/index.html", "a.js?v=1").unwrap(), "/a.js");
+        assert_eq!(
+            resolve_route("/blog/index.html", "./assets/a.js").unwrap(),
+            "/blog/assets/a.js"
+        );
+        assert_eq!(
+            resolve_route("/blog/post/index.html", "../../a.js#x").unwrap(),
+            "/a.js"
+        );
+        assert!(resolve_route("/index.html", "https://example.com/a.js").is_none());
+        assert!(resolve_route("/index.html", "//example.com/a.js").is_none());
+    }
+
+    #[test]
+    fn integrity() {
+        let html = b"<script src='/a.js'></script><script>1</script><link rel=\"icon\" href=\"/a.js\"><LINK REL=stylesheet HREF=b.css><script src=\"/a.js\" integrity=\"x\"></script><script src=\"/c.js\"></script>";
+        let lookup = |route: &str| match route {
+            "/a.js" => Some("sha384-a"),
+            "/b.css" => Some("sha384-b"),
+            _ => None,
+        };
+
+        assert_eq!(
+            String::from_utf8(insert_integrity(html, "/index.html", lookup).unwrap()).unwrap(),
+            "<script integrity=\"sha384-a\" src='/a.js'></script><script>1</script><link rel=\"icon\" href=\"/a.js\"><LINK integrity=\"sha384-b\" REL=stylesheet HREF=b.css><script src=\"/a.js\" integrity=\"x\"></script><script src=\"/c.js\"></script>"
+        );
+        assert!(insert_integrity(b"<script>1</script>", "/index.html", lookup).is_none());
+    }
+}
@@ -0,0 +1,20 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha2::{Digest, Sha384};
+
+/// Compute a Subresource Integrity string, e.g. `sha384-OLBgp1...`.
+pub(crate) fn sri_hash(bytes: &[u8]) -> String {
+    format!("sha384-{}", STANDARD.encode(Sha384::digest(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sri_hash;
+
+    #[test]
+    fn sri() {
+        assert_eq!(
+            sri_hash(b""),
+            "sha384-OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb"
+        );
+    }
+}
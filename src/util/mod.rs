@@ -1,3 +1,9 @@
+pub(crate) mod blocking;
 pub(crate) mod compression;
 pub(crate) mod headers;
+pub(crate) mod html;
+pub(crate) mod integrity;
+pub(crate) mod pattern;
 pub(crate) mod route;
+pub(crate) mod sniff;
+pub(crate) mod stream;
@@ -0,0 +1,126 @@
+/// Check whether the input matches a pattern in which `*` matches any
+/// (possibly empty) sequence of characters.
+pub(crate) fn glob_match(pattern: &str, input: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let input = input.as_bytes();
+
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < input.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, i));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == input[i] {
+            p += 1;
+            i += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            i = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Match a path against a pattern with `:name` placeholders, matching a
+/// single path segment, and an optional trailing `*` splat, matching the
+/// remainder of the path. Returns the captured values, the splat is
+/// captured as `splat`.
+pub(crate) fn match_placeholders<'p>(
+    pattern: &'p str,
+    path: &str,
+) -> Option<Vec<(&'p str, String)>> {
+    let mut captures = Vec::new();
+    let mut segments = path.trim_start_matches('/').split('/');
+
+    for part in pattern.trim_start_matches('/').split('/') {
+        if part == "*" {
+            let rest: Vec<&str> = segments.by_ref().collect();
+            captures.push(("splat", rest.join("/")));
+
+            return Some(captures);
+        }
+
+        let segment = segments.next()?;
+
+        if let Some(name) = part.strip_prefix(':') {
+            if segment.is_empty() {
+                return None;
+            }
+
+            captures.push((name, segment.to_string()));
+        } else if part != segment {
+            return None;
+        }
+    }
+
+    match segments.next() {
+        // allow a trailing slash
+        None | Some("") if segments.next().is_none() => Some(captures),
+        _ => None,
+    }
+}
+
+/// Replace the `:name` placeholders in a template by the captured values.
+pub(crate) fn fill_placeholders(template: &str, captures: &[(&str, String)]) -> String {
+    let mut captures: Vec<&(&str, String)> = captures.iter().collect();
+
+    // replace longer names first, so `:id` does not replace a part of `:idx`
+    captures.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    captures
+        .into_iter()
+        .fold(template.to_string(), |result, (name, value)| {
+            result.replace(&format!(":{name}"), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_placeholders, glob_match, match_placeholders};
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "/index.html"));
+        assert!(glob_match("/index.html", "/index.html"));
+        assert!(!glob_match("/index.html", "/index.htm"));
+        assert!(glob_match("/assets/*", "/assets/icon.jpg"));
+        assert!(glob_match("/assets/*", "/assets/"));
+        assert!(!glob_match("/assets/*", "/assets"));
+        assert!(glob_match("*.html", "/blog/index.html"));
+        assert!(glob_match("/*/index.html", "/blog/index.html"));
+        assert!(glob_match("text/*", "text/html"));
+        assert!(!glob_match("text/*", "image/svg+xml"));
+        assert!(glob_match("/a*b*c", "/aXbYbZc"));
+        assert!(!glob_match("/a*b*c", "/aXbYbZ"));
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(match_placeholders("/a", "/a"), Some(vec![]));
+        assert_eq!(match_placeholders("/a", "/a/"), Some(vec![]));
+        assert_eq!(match_placeholders("/a", "/a/b"), None);
+        assert_eq!(match_placeholders("/a", "/b"), None);
+        assert_eq!(
+            match_placeholders("/blog/*", "/blog/2024/post"),
+            Some(vec![("splat", "2024/post".to_string())])
+        );
+        assert_eq!(
+            match_placeholders("/blog/*", "/blog"),
+            Some(vec![("splat", "".to_string())])
+        );
+        assert_eq!(
+            match_placeholders("/news/:year/:id", "/news/2024/42"),
+            Some(vec![("year", "2024".to_string()), ("id", "42".to_string())])
+        );
+        assert_eq!(match_placeholders("/news/:year/:id", "/news/2024"), None);
+
+        let captures = [("id", "1".to_string()), ("idx", "2".to_string())];
+        assert_eq!(fill_placeholders("/:idx/:id", &captures), "/2/1");
+    }
+}
@@ -23,8 +23,9 @@ const ALLOWED_CHARS: [(&str, &str); 19] = [
     ("%5D", "]"),
 ];
 
-/// Convert a path to a (HTTP) path / route
-pub(crate) fn path_to_route(base: &Path, path: &Path) -> String {
+/// Convert a path to a (HTTP) path / route, returns `None` when the path
+/// is not valid UTF-8
+pub(crate) fn path_to_route(base: &Path, path: &Path) -> Option<String> {
     let relative_path = path
         .strip_prefix(base)
         .expect("Could not strip prefix from path");
@@ -32,10 +33,10 @@ pub(crate) fn path_to_route(base: &Path, path: &Path) -> String {
     let route = relative_path
         .components()
         .filter_map(|c| match c {
-            std::path::Component::Normal(s) => s.to_str(),
+            std::path::Component::Normal(s) => Some(s.to_str()),
             _ => None,
         })
-        .collect::<Vec<&str>>()
+        .collect::<Option<Vec<&str>>>()?
         .join("/");
 
     let mut route: String = urlencoding::encode(&route).to_string();
@@ -44,24 +45,74 @@ pub(crate) fn path_to_route(base: &Path, path: &Path) -> String {
         route = route.replace(from, to);
     }
 
-    format!("/{route}")
+    Some(format!("/{route}"))
 }
 
+/// MIME types for extensions `mime_guess` does not know or gets wrong
+const EXTENSION_TYPES: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("wasm", "application/wasm"),
+    ("webmanifest", "application/manifest+json"),
+];
+
+/// MIME types, besides `text/*`, that contain text
+const TEXT_TYPES: &[&str] = &[
+    "application/javascript",
+    "application/json",
+    "application/ld+json",
+    "application/manifest+json",
+    "application/xml",
+];
+
 /// Determine the mime type of a file
 pub(crate) fn path_to_content_type(path: &Path) -> Option<String> {
-    let ext = path.extension()?;
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+
+    if let Some((_, content_type)) = EXTENSION_TYPES.iter().find(|(e, _)| *e == ext) {
+        return Some(content_type.to_string());
+    }
 
     Some(
-        mime_guess::from_ext(&ext.to_string_lossy())
+        mime_guess::from_ext(&ext)
             .first_raw()
             .unwrap_or(mime::APPLICATION_OCTET_STREAM.to_string().as_str())
             .to_owned(),
     )
 }
 
+/// The MIME type without parameters, e.g. `text/html` for
+/// `text/html; charset=utf-8`
+pub(crate) fn essence(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or_default().trim()
+}
+
+/// Whether a MIME type (without parameters) is a text format, which should
+/// be served with a charset
+pub(crate) fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/") || TEXT_TYPES.contains(&content_type)
+}
+
 #[cfg(test)]
 mod test {
-    use super::path_to_route;
+    use super::{essence, path_to_content_type, path_to_route};
+    use std::path::Path;
+
+    #[test]
+    fn content_types() {
+        let content_type = |path: &str| path_to_content_type(Path::new(path));
+
+        assert_eq!(
+            content_type("/app.webmanifest").unwrap(),
+            "application/manifest+json"
+        );
+        assert_eq!(content_type("/index.mjs").unwrap(), "text/javascript");
+        assert_eq!(content_type("/main.wasm").unwrap(), "application/wasm");
+        assert_eq!(content_type("/image.AVIF").unwrap(), "image/avif");
+        assert_eq!(content_type("/LICENSE"), None);
+        assert_eq!(essence("text/html; charset=utf-8"), "text/html");
+    }
 
     #[test]
     fn test_path_to_route() {
@@ -71,7 +122,7 @@ mod test {
         );
 
         assert_eq!(
-            path_to_route(base, path),
+            path_to_route(base, path).unwrap(),
             "/assets/stars:wow%20!@%%5E&*()ama%7Bzi%7Dng%F0%9F%92%A9!%20*%20'%20(%20)%20;%20:%20@%20&%20=%20+%20$%20,%20?%20%%20[%20]%20\\.svg"
         );
     }
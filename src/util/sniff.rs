@@ -0,0 +1,87 @@
+/// Number of bytes inspected to determine the content type.
+pub(crate) const SNIFF_LENGTH: usize = 512;
+
+/// Signatures of binary formats, with their MIME type.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\0asm", "application/wasm"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"wOF2", "font/woff2"),
+    (b"wOFF", "font/woff"),
+];
+
+/// Determine the MIME type of a file from (the start of) its contents,
+/// falling back to `text/plain` for UTF-8 text and
+/// `application/octet-stream` for other binary data.
+pub(crate) fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    let bytes = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+    {
+        return content_type;
+    }
+
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    // the input can be cut off in the middle of a multi-byte character
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return "application/octet-stream",
+    };
+
+    if text
+        .chars()
+        .any(|c| c.is_control() && !c.is_ascii_whitespace())
+    {
+        return "application/octet-stream";
+    }
+
+    let start = text.trim_start().to_ascii_lowercase();
+
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "text/html"
+    } else if start.starts_with("<svg") {
+        "image/svg+xml"
+    } else if start.starts_with("<?xml") {
+        "application/xml"
+    } else if start.starts_with('{') || start.starts_with('[') {
+        "application/json"
+    } else {
+        "text/plain"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sniff_content_type;
+
+    #[test]
+    fn sniff() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_content_type(b"  <!DOCTYPE html><p>"), "text/html");
+        assert_eq!(
+            sniff_content_type(b"{\"applinks\": {}}"),
+            "application/json"
+        );
+        assert_eq!(sniff_content_type(b"example.com\n"), "text/plain");
+        assert_eq!(sniff_content_type("caf\u{e9}".as_bytes()), "text/plain");
+        assert_eq!(
+            sniff_content_type(b"\0\x01\x02"),
+            "application/octet-stream"
+        );
+        assert_eq!(sniff_content_type(b""), "text/plain");
+    }
+}
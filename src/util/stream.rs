@@ -0,0 +1,197 @@
+use axum::body::Bytes;
+use futures_util::{Stream, stream};
+use std::io::{self, Write};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, Take},
+};
+
+use super::blocking::spawn_blocking;
+
+/// Number of bytes read from disk at a time when streaming a file.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Brotli quality used to compress streamed files, which are compressed for
+/// every request.
+const STREAM_BROTLI_QUALITY: u32 = 5;
+
+/// Compresses a stream of chunks, the compressed output is taken from the
+/// buffer after every chunk.
+pub(crate) enum StreamEncoder {
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+}
+
+impl StreamEncoder {
+    /// Create a brotli encoder.
+    pub(crate) fn brotli() -> Self {
+        Self::Brotli(Box::new(brotli::CompressorWriter::new(
+            Vec::new(),
+            4096,
+            STREAM_BROTLI_QUALITY,
+            22,
+        )))
+    }
+
+    /// Create a gzip encoder.
+    pub(crate) fn gzip() -> Self {
+        Self::Gzip(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ))
+    }
+
+    /// Compress a chunk, returning the output that is available so far.
+    fn write(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        let buffer = match self {
+            Self::Brotli(writer) => {
+                writer.write_all(chunk)?;
+                writer.get_mut()
+            }
+            Self::Gzip(writer) => {
+                writer.write_all(chunk)?;
+                writer.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(buffer).into())
+    }
+
+    /// Finish the compressed stream, returning the remaining output.
+    fn finish(self) -> io::Result<Bytes> {
+        let buffer = match self {
+            Self::Brotli(writer) => writer.into_inner(),
+            Self::Gzip(writer) => writer.finish()?,
+        };
+
+        Ok(buffer.into())
+    }
+}
+
+/// State of a streamed file.
+struct FileStream {
+    file: Take<File>,
+    /// Whether the stream should contain exactly the number of bytes of
+    /// the limit, e.g. because it was sent as `Content-Length`.
+    exact: bool,
+    encoder: Option<StreamEncoder>,
+    buffer: Vec<u8>,
+}
+
+/// Stream the contents of a file in chunks, compressed on the blocking thread
+/// pool when an encoder is given. With a length, the stream ends after that many bytes, or fails when
+/// the file is shorter, e.g. because it was truncated after sending the
+/// `Content-Length`.
+pub(crate) fn file_stream(
+    file: File,
+    length: Option<u64>,
+    encoder: Option<StreamEncoder>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send {
+    let state = FileStream {
+        file: file.take(length.unwrap_or(u64::MAX)),
+        exact: length.is_some(),
+        encoder,
+        buffer: Vec::with_capacity(CHUNK_SIZE),
+    };
+
+    stream::try_unfold(Some(state), |state| async move {
+        let Some(mut state) = state else {
+            return Ok(None);
+        };
+
+        loop {
+            state.buffer.clear();
+
+            if state.file.read_buf(&mut state.buffer).await? == 0 {
+                if state.exact && state.file.limit() > 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file is shorter than its announced length",
+                    ));
+                }
+
+                return match state.encoder.take() {
+                    Some(encoder) => Ok(Some((spawn_blocking(|| encoder.finish()).await?, None))),
+                    None => Ok(None),
+                };
+            }
+
+            let chunk = match state.encoder.take() {
+                Some(mut encoder) => {
+                    let buffer = std::mem::take(&mut state.buffer);
+                    let (encoder, buffer, chunk) = spawn_blocking(move || {
+                        let chunk = encoder.write(&buffer);
+
+                        (encoder, buffer, chunk)
+                    })
+                    .await;
+
+                    state.encoder = Some(encoder);
+                    state.buffer = buffer;
+
+                    chunk?
+                }
+                None => Bytes::copy_from_slice(&state.buffer),
+            };
+
+            // the encoder can buffer small chunks without producing output
+            if !chunk.is_empty() {
+                return Ok(Some((chunk, Some(state))));
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::{StreamEncoder, file_stream};
+    use crate::util::compression::decompress_brotli;
+
+    async fn try_collect(
+        length: Option<u64>,
+        encoder: Option<StreamEncoder>,
+    ) -> std::io::Result<Vec<u8>> {
+        let file = tokio::fs::File::open("./static/assets/stars.svg")
+            .await
+            .unwrap();
+
+        file_stream(file, length, encoder)
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+    }
+
+    async fn collect(encoder: Option<StreamEncoder>) -> Vec<u8> {
+        try_collect(None, encoder).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let bytes = std::fs::read("./static/assets/stars.svg").unwrap();
+
+        assert_eq!(collect(None).await, bytes);
+
+        let compressed = collect(Some(StreamEncoder::brotli())).await;
+        assert_eq!(decompress_brotli(&compressed).unwrap(), bytes);
+
+        let compressed = collect(Some(StreamEncoder::gzip())).await;
+        let mut decoder = flate2::read::GzDecoder::new(&compressed[..]);
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+        assert_eq!(decompressed, bytes);
+    }
+
+    #[tokio::test]
+    async fn announced_length() {
+        let bytes = std::fs::read("./static/assets/stars.svg").unwrap();
+        let len = bytes.len() as u64;
+
+        assert_eq!(try_collect(Some(len), None).await.unwrap(), bytes);
+        // a file that grew is cut off at the announced length
+        assert_eq!(try_collect(Some(10), None).await.unwrap(), &bytes[..10]);
+        // a file that was truncated fails the stream
+        assert!(try_collect(Some(len + 1), None).await.is_err());
+    }
+}
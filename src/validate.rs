@@ -0,0 +1,131 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{asset::Asset, canonical::AssetPaths, options::ServeOptions};
+
+/// A problem in the configuration of a `MemoryServe` instance, see
+/// [`MemoryServe::try_into_router`](crate::MemoryServe::try_into_router).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// Multiple assets, aliases or canonical redirects are registered on the
+    /// same path, e.g. `/about` for `/about.html` with clean URLs and for
+    /// `/about/index.html` as subdirectory index.
+    Collision {
+        path: String,
+        first: String,
+        second: String,
+    },
+    /// An alias points to a route without asset.
+    MissingAliasTarget {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// The index file set with `MemoryServe::index_file` matches no asset.
+    MissingIndex(&'static str),
+    /// The fallback matches no asset.
+    MissingFallback(&'static str),
+    /// The route of an SPA fallback matches no asset.
+    MissingSpaFallback {
+        prefix: &'static str,
+        route: &'static str,
+    },
+}
+
+impl RouteError {
+    /// Whether registering the routes would fail.
+    pub(crate) fn is_fatal(&self) -> bool {
+        matches!(self, Self::Collision { .. })
+    }
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Collision {
+                path,
+                first,
+                second,
+            } => write!(f, "path {path} is used by both {first} and {second}"),
+            Self::MissingAliasTarget { from, to } => {
+                write!(f, "alias {from} points to {to}, which matches no asset")
+            }
+            Self::MissingIndex(index) => write!(f, "index file {index} matches no asset"),
+            Self::MissingFallback(fallback) => write!(f, "fallback {fallback} matches no asset"),
+            Self::MissingSpaFallback { prefix, route } => {
+                write!(f, "SPA fallback {route} on {prefix} matches no asset")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Check the paths that would be registered for the assets (with the
+/// paths of each asset in the same order) and aliases, and the routes used
+/// in the options.
+pub(crate) fn validate(
+    assets: &[Asset],
+    asset_paths: &[AssetPaths],
+    aliases: &[(&'static str, &'static str)],
+    options: &ServeOptions,
+) -> Vec<RouteError> {
+    let mut errors = Vec::new();
+    let mut paths: HashMap<&'static str, String> = HashMap::new();
+    let exists = |route: &str| assets.iter().any(|asset| asset.route == route);
+
+    let mut register = |path: &'static str, source: String, errors: &mut Vec<RouteError>| {
+        if let Some(first) = paths.get(path) {
+            errors.push(RouteError::Collision {
+                path: path.to_string(),
+                first: first.clone(),
+                second: source,
+            });
+        } else {
+            paths.insert(path, source);
+        }
+    };
+
+    for (asset, asset_paths) in assets.iter().zip(asset_paths) {
+        for path in &asset_paths.served {
+            register(path, format!("asset {}", asset.route), &mut errors);
+        }
+
+        for (from, to) in &asset_paths.redirects {
+            register(from, format!("redirect to {to}"), &mut errors);
+        }
+    }
+
+    for (from, to) in aliases {
+        if exists(to) {
+            register(from, format!("alias to {to}"), &mut errors);
+        } else {
+            errors.push(RouteError::MissingAliasTarget { from, to });
+        }
+    }
+
+    if let Some(index) = options.index_file
+        && options.explicit_index_file
+    {
+        let found = assets.iter().any(|asset| {
+            asset.route == index
+                || (options.index_on_subdirectories && asset.route.ends_with(index))
+        });
+
+        if !found {
+            errors.push(RouteError::MissingIndex(index));
+        }
+    }
+
+    if let Some(fallback) = options.fallback
+        && !exists(fallback)
+    {
+        errors.push(RouteError::MissingFallback(fallback));
+    }
+
+    for (prefix, route) in &options.spa_fallbacks {
+        if !exists(route) {
+            errors.push(RouteError::MissingSpaFallback { prefix, route });
+        }
+    }
+
+    errors
+}